@@ -0,0 +1,317 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use chess::{parse_square, ChessBoard, Move, Square};
+use chess::pgn::{parse_pgn, PgnGame};
+use chess::render::{render, RenderOptions};
+use chess::search::{best_move_with_table, Score, SearchLimits, DEFAULT_HASH_MB};
+use chess::tt::TranspositionTable;
+
+const DEFAULT_LEVEL: u32 = 4;
+const MAX_LEVEL: u32 = 20;
+// Each level may think this much longer, so high levels do not take forever in the middlegame
+const MILLIS_PER_LEVEL: u64 = 250;
+
+const HELP: &str = "\
+commands:
+  <move>            play a move in SAN (Nf3, exd5, O-O), UCI (g1f3) or coordinates (g1-f3)
+  moves             list the legal moves
+  undo              take back the last move, or the last two against the engine
+  new               start a new game
+  fen [FEN]         show the position as FEN, or set it
+  pgn               show the game as PGN
+  load FILE         load a .fen or .pgn file
+  save FILE         save the game as .fen or .pgn
+  play white|black|both|none
+                    the side(s) you play, the engine plays the rest
+  level N           engine strength 1-20, the search depth
+  go                let the engine play the side to move
+  board             show the board
+  flip              turn the board around
+  unicode           switch between letters and chess symbols
+  colors            switch terminal colors on or off
+  help              show this text
+  quit              leave";
+
+struct Cli {
+    start: ChessBoard,
+    board: ChessBoard,
+    moves: Vec<Move>,
+    // The positions before each move, for undo
+    history: Vec<ChessBoard>,
+    tags: Vec<(String, String)>,
+    human_white: bool,
+    human_black: bool,
+    level: u32,
+    render: RenderOptions,
+    tt: TranspositionTable,
+}
+
+impl Cli {
+    fn new() -> Cli {
+        Cli {
+            start: ChessBoard::new(),
+            board: ChessBoard::new(),
+            moves: Vec::new(),
+            history: Vec::new(),
+            tags: Vec::new(),
+            human_white: true,
+            human_black: false,
+            level: DEFAULT_LEVEL,
+            render: RenderOptions::default(),
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+        }
+    }
+
+    // Returns false once the program should exit
+    fn handle(&mut self, line: &str) -> bool {
+        let (command, argument): (&str, &str) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        match command {
+            "" => (),
+            "help" | "?" => println!("{}", HELP),
+            "quit" | "exit" => return false,
+            "board" => self.show_board(),
+            "flip" => {
+                self.render.flipped = !self.render.flipped;
+                self.show_board();
+            },
+            "unicode" => {
+                self.render.unicode = !self.render.unicode;
+                self.show_board();
+            },
+            "colors" => {
+                self.render.ansi_colors = !self.render.ansi_colors;
+                self.show_board();
+            },
+            "moves" => {
+                let mut moves: Vec<String> = self.board.generate_moves().iter().map(|m| self.board.to_san(m)).collect();
+                moves.sort();
+                println!("{}", moves.join(" "));
+            },
+            "undo" => self.undo(),
+            "new" => {
+                self.set_position(ChessBoard::new(), Vec::new());
+                self.tt.clear();
+                self.show_board();
+                self.engine_turn();
+            },
+            "fen" if argument.is_empty() => println!("{}", self.board.to_fen()),
+            "fen" => match ChessBoard::new_fen(argument) {
+                Ok(board) => {
+                    self.set_position(board, Vec::new());
+                    self.show_board();
+                },
+                Err(error) => println!("invalid fen: {:?}", error),
+            },
+            "pgn" => print!("{}", self.pgn().to_pgn()),
+            "load" => match self.load(argument) {
+                Ok(()) => self.show_board(),
+                Err(message) => println!("{}", message),
+            },
+            "save" => match self.save(argument) {
+                Ok(()) => println!("saved {}", argument),
+                Err(message) => println!("{}", message),
+            },
+            "play" => {
+                match argument {
+                    "white" => (self.human_white, self.human_black) = (true, false),
+                    "black" => (self.human_white, self.human_black) = (false, true),
+                    "both" => (self.human_white, self.human_black) = (true, true),
+                    "none" => (self.human_white, self.human_black) = (false, false),
+                    _ => {
+                        println!("expected white, black, both or none");
+                        return true;
+                    },
+                }
+                self.engine_turn();
+            },
+            "level" => match argument.parse::<u32>() {
+                Ok(level) if (1..=MAX_LEVEL).contains(&level) => {
+                    self.level = level;
+                    println!("level {}", level);
+                },
+                _ => println!("expected a level from 1 to {}", MAX_LEVEL),
+            },
+            "go" => {
+                if self.game_over().is_none() { self.engine_move(); }
+                self.engine_turn();
+            },
+            _ => match self.parse_move(line) {
+                Some(m) => {
+                    self.play(m);
+                    self.engine_turn();
+                },
+                None => println!("unknown command or illegal move: {}", line),
+            },
+        }
+        true
+    }
+
+    fn set_position(&mut self, start: ChessBoard, moves: Vec<Move>) {
+        self.start = start;
+        self.board = start;
+        self.moves.clear();
+        self.history.clear();
+        self.tags.clear();
+        for m in moves {
+            self.history.push(self.board);
+            self.board.make_move(m, false);
+            self.moves.push(m);
+        }
+    }
+
+    // SAN first, then UCI, then two squares with a separator like g1-f3 or G1 F3
+    fn parse_move(&self, value: &str) -> Option<Move> {
+        if let Some(m) = self.board.parse_san(value) { return Some(m); }
+        let value: String = value.to_lowercase();
+        if let Some(m) = self.board.parse_uci_move(&value) { return Some(m); }
+        let squares: String = value.chars().filter(|c| !"- x".contains(*c)).collect();
+        let from: Square = parse_square(squares.get(0..2)?)?;
+        let to: Square = parse_square(squares.get(2..4)?)?;
+        let promotion: &str = squares.get(4..).unwrap_or("").trim_start_matches('=');
+        self.board.parse_uci_move(&format!("{}{}{}", square_name(from), square_name(to), promotion))
+    }
+
+    fn play(&mut self, m: Move) {
+        let san: String = self.board.to_san(&m);
+        let number: String = if self.board.white_turn {
+            format!("{}.", self.board.fullmove_number)
+        } else {
+            format!("{}...", self.board.fullmove_number)
+        };
+        self.history.push(self.board);
+        self.board.make_move(m, false);
+        self.moves.push(m);
+        println!("{} {}", number, san);
+        self.show_board();
+        if let Some(result) = self.game_over() {
+            println!("{}", result);
+        }
+    }
+
+    fn is_engine_turn(&self) -> bool {
+        if self.board.white_turn { !self.human_white } else { !self.human_black }
+    }
+
+    // Plays engine moves until it is the human's turn or the game is over
+    fn engine_turn(&mut self) {
+        while self.is_engine_turn() && self.game_over().is_none() {
+            // Without a human the engine would play the whole game at once, one move per go is enough
+            if !self.human_white && !self.human_black { break; }
+            self.engine_move();
+        }
+    }
+
+    fn engine_move(&mut self) {
+        let mut limits: SearchLimits = SearchLimits::depth(self.level);
+        limits.movetime = Some(self.level as u64 * MILLIS_PER_LEVEL);
+        let result = best_move_with_table(&self.board, limits, &mut self.tt);
+        if let Some(m) = result.best_move {
+            let score: String = match result.score {
+                Score::Centipawns(value) => format!("{:+.2}", value as f64 / 100.0),
+                Score::Mate(moves) => format!("mate {}", moves),
+                Score::Tablebase(plies) if plies > 0 => format!("tablebase win in {} plies", plies),
+                Score::Tablebase(plies) => format!("tablebase loss in {} plies", -plies),
+            };
+            println!("engine plays {} ({}, depth {})", self.board.to_san(&m), score, result.depth);
+            self.play(m);
+        }
+    }
+
+    fn undo(&mut self) {
+        let plies: usize = if self.human_white != self.human_black && self.is_human_turn_after_undo(2) { 2 } else { 1 };
+        if self.history.len() < plies {
+            println!("nothing to undo");
+            return;
+        }
+        for _ in 0..plies {
+            self.board = self.history.pop().unwrap();
+            self.moves.pop();
+        }
+        self.show_board();
+    }
+
+    // Against the engine a single undo would give the engine the move again
+    fn is_human_turn_after_undo(&self, plies: usize) -> bool {
+        if self.history.len() < plies { return false; }
+        let board: ChessBoard = self.history[self.history.len() - plies];
+        if board.white_turn { self.human_white } else { self.human_black }
+    }
+
+    fn game_over(&self) -> Option<&'static str> {
+        if self.board.is_white_mated() { return Some("0-1 {black mates}"); }
+        if self.board.is_black_mated() { return Some("1-0 {white mates}"); }
+        if self.board.is_white_stalemate() || self.board.is_black_stalemate() { return Some("1/2-1/2 {stalemate}"); }
+        if self.board.is_insufficient_material() { return Some("1/2-1/2 {insufficient material}"); }
+        if self.board.halfmove_clock >= 100 { return Some("1/2-1/2 {fifty move rule}"); }
+        None
+    }
+
+    fn pgn(&self) -> PgnGame {
+        let mut game: PgnGame = PgnGame::from_moves(&self.start, &self.moves);
+        for (name, value) in self.tags.iter() {
+            if name != "FEN" && name != "SetUp" { game.set_tag(name, value); }
+        }
+        game.result = match self.game_over() {
+            Some(result) => result.split(' ').next().unwrap().to_string(),
+            None => "*".to_string(),
+        };
+        game
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let text: String = fs::read_to_string(path).map_err(|e| format!("can not read {}: {}", path, e))?;
+        if path.to_lowercase().ends_with(".pgn") {
+            let games: Vec<PgnGame> = parse_pgn(&text).map_err(|e| format!("invalid pgn: {:?}", e))?;
+            let game: &PgnGame = games.first().ok_or("no game in file".to_string())?;
+            let start: ChessBoard = game.start_position().map_err(|e| format!("invalid pgn: {:?}", e))?;
+            let moves: Vec<Move> = game.board_moves().map_err(|e| format!("invalid pgn: {:?}", e))?;
+            self.set_position(start, moves);
+            self.tags = game.tags.clone();
+        } else {
+            let board: ChessBoard = ChessBoard::new_fen(text.trim()).map_err(|e| format!("invalid fen: {:?}", e))?;
+            self.set_position(board, Vec::new());
+        }
+        Ok(())
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        if path.is_empty() { return Err("expected a file name".to_string()); }
+        let text: String = if path.to_lowercase().ends_with(".pgn") {
+            self.pgn().to_pgn()
+        } else {
+            format!("{}\n", self.board.to_fen())
+        };
+        fs::write(path, text).map_err(|e| format!("can not write {}: {}", path, e))
+    }
+
+    fn show_board(&self) {
+        let options: RenderOptions = RenderOptions { last_move: self.moves.last().copied(), ..self.render };
+        print!("{}", render(&self.board, &options));
+        println!("{} to move", if self.board.white_turn { "white" } else { "black" });
+    }
+}
+
+fn square_name(square: Square) -> String {
+    square.to_string().to_lowercase()
+}
+
+fn main() {
+    let mut cli: Cli = Cli::new();
+    println!("type help for the commands");
+    cli.show_board();
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line: String = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        if !cli.handle(line.trim()) { break; }
+    }
+}
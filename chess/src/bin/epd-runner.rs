@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use chess::epd::{parse_epd, EpdRecord};
+use chess::search::{best_move_with_table, Score, SearchLimits, SearchResult, DEFAULT_HASH_MB};
+use chess::tt::TranspositionTable;
+
+const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+const USAGE: &str = "\
+usage: epd-runner FILE [options]
+  --depth N         search N plies deep
+  --nodes N         search at most N nodes
+  --movetime MS     search MS milliseconds per position (the default is 1000 without other limits)
+  --hash MB         size of the transposition table
+  --quiet           only print the failed positions and the summary";
+
+struct Options {
+    path: String,
+    limits: SearchLimits,
+    hash_mb: usize,
+    quiet: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options: Options = Options { path: String::new(), limits: SearchLimits::default(), hash_mb: DEFAULT_HASH_MB, quiet: false };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<u64, String> {
+            args.next().and_then(|v| v.parse::<u64>().ok()).ok_or(format!("{} needs a number", name))
+        };
+        match arg.as_str() {
+            "--depth" => options.limits.depth = Some(value(arg)? as u32),
+            "--nodes" => options.limits.nodes = Some(value(arg)?),
+            "--movetime" => options.limits.movetime = Some(value(arg)?),
+            "--hash" => options.hash_mb = value(arg)?.max(1) as usize,
+            "--quiet" => options.quiet = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.path.is_empty() => options.path = arg.clone(),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    if options.path.is_empty() { return Err("no file given".to_string()); }
+    if options.limits.depth.is_none() && options.limits.nodes.is_none() && options.limits.movetime.is_none() {
+        options.limits.movetime = Some(DEFAULT_MOVETIME_MS);
+    }
+    Ok(options)
+}
+
+fn score_text(score: &Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("cp {}", cp),
+        Score::Mate(moves) => format!("mate {}", moves),
+        Score::Tablebase(plies) => format!("tb {}", plies),
+    }
+}
+
+// What the position asks for, like "bm Qg6" or "bm e4 d4 am f3"
+fn expected_text(record: &EpdRecord) -> String {
+    ["bm", "am"].iter()
+        .filter_map(|opcode| record.operation(opcode).map(|moves| format!("{} {}", opcode, moves.join(" "))))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options: Options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    let text: String = match fs::read_to_string(&options.path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("can not read {}: {}", options.path, error);
+            process::exit(1);
+        }
+    };
+
+    let (mut solved, mut failed, mut skipped): (u32, u32, u32) = (0, 0, 0);
+    let mut nodes: u64 = 0;
+    for (number, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let record: EpdRecord = match parse_epd(line) {
+            Ok(record) => record,
+            Err(error) => {
+                println!("line {}: skipped, {:?}", number + 1, error);
+                skipped += 1;
+                continue;
+            }
+        };
+        let id: String = record.id().map_or(format!("line {}", number + 1), |id| id.to_string());
+        if record.operation("bm").is_none() && record.operation("am").is_none() {
+            println!("{}: skipped, no bm or am", id);
+            skipped += 1;
+            continue;
+        }
+
+        // A new table for every position, so the results do not depend on the order of the suite
+        let mut tt: TranspositionTable = TranspositionTable::new(options.hash_mb);
+        let result: SearchResult = best_move_with_table(&record.board, options.limits, &mut tt);
+        nodes += result.nodes;
+        let Some(m) = result.best_move else {
+            println!("{}: skipped, no legal moves", id);
+            skipped += 1;
+            continue;
+        };
+        let found: String = record.board.to_san(&m);
+        match record.is_solved(&m) {
+            Ok(true) => {
+                solved += 1;
+                if !options.quiet {
+                    println!("{}: solved {} ({}) {} depth {} nodes {}", id, found, expected_text(&record), score_text(&result.score), result.depth, result.nodes);
+                }
+            },
+            Ok(false) => {
+                failed += 1;
+                println!("{}: failed {} ({}) {} depth {} nodes {}", id, found, expected_text(&record), score_text(&result.score), result.depth, result.nodes);
+            },
+            Err(error) => {
+                println!("{}: skipped, {:?}", id, error);
+                skipped += 1;
+            },
+        }
+    }
+
+    let total: u32 = solved + failed;
+    let percent: f64 = if total > 0 { solved as f64 * 100.0 / total as f64 } else { 0.0 };
+    println!("solved {} of {} ({:.1}%), failed {}, skipped {}, nodes {}", solved, total, percent, failed, skipped, nodes);
+}
@@ -0,0 +1,288 @@
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chess::{ChessBoard, Move};
+use chess::polyglot::PolyglotBook;
+use chess::search::{search_with_tablebase, Score, SearchInfo, SearchLimits, DEFAULT_HASH_MB, TB_WIN_CENTIPAWNS};
+use chess::syzygy::Tablebase;
+use chess::timeman::{StopFlag, TimeManager};
+use chess::tt::TranspositionTable;
+
+const NAME: &str = "castorm-chess";
+const AUTHOR: &str = "INDA22PlusPlus";
+const MAX_HASH_MB: usize = 4096;
+
+struct Engine {
+    board: ChessBoard,
+    tt: Arc<Mutex<TranspositionTable>>,
+    hash_mb: usize,
+    chess960: bool,
+    // Every search gets its own flag, so a late ponderhit timer can not stop the next one
+    stop: StopFlag,
+    search: Option<JoinHandle<()>>,
+    infinite: bool,
+    // The real limits of a ponder search, used once the opponent plays the expected move
+    ponder_limits: Option<SearchLimits>,
+    book: Option<PolyglotBook>,
+    own_book: bool,
+    tablebase: Option<Arc<Tablebase>>,
+}
+
+impl Engine {
+    fn new() -> Engine {
+        Engine {
+            board: ChessBoard::new(),
+            tt: Arc::new(Mutex::new(TranspositionTable::new(DEFAULT_HASH_MB))),
+            hash_mb: DEFAULT_HASH_MB,
+            chess960: false,
+            stop: StopFlag::new(),
+            search: None,
+            infinite: false,
+            ponder_limits: None,
+            book: None,
+            own_book: false,
+            tablebase: None,
+        }
+    }
+
+    // Returns false once the engine should exit
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name {}", NAME);
+                println!("id author {}", AUTHOR);
+                println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB);
+                println!("option name Clear Hash type button");
+                println!("option name Ponder type check default false");
+                println!("option name UCI_Chess960 type check default false");
+                println!("option name OwnBook type check default false");
+                println!("option name Book File type string default <empty>");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("uciok");
+            },
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                self.wait();
+                self.tt.lock().unwrap().clear();
+                self.board = self.new_board();
+            },
+            Some("position") => {
+                self.wait();
+                if let Err(message) = self.position(&tokens[1..]) {
+                    println!("info string {}", message);
+                }
+            },
+            Some("go") => {
+                self.wait();
+                self.go(&tokens[1..]);
+            },
+            Some("stop") => {
+                self.stop.stop();
+                self.wait();
+            },
+            Some("ponderhit") => self.ponderhit(),
+            Some("setoption") => self.set_option(&tokens[1..]),
+            Some("quit") => {
+                self.stop.stop();
+                self.wait();
+                return false;
+            },
+            Some(command) => println!("info string unknown command {}", command),
+            None => (),
+        }
+        true
+    }
+
+    fn wait(&mut self) {
+        if let Some(handle) = self.search.take() {
+            handle.join().unwrap();
+        }
+    }
+
+    fn new_board(&self) -> ChessBoard {
+        let mut board: ChessBoard = ChessBoard::new();
+        board.chess960 = self.chess960;
+        board
+    }
+
+    fn position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_index: usize = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+        let mut board: ChessBoard = match tokens.first().copied() {
+            Some("startpos") => self.new_board(),
+            Some("fen") => {
+                let fen: String = tokens[1..moves_index].join(" ");
+                let mut board: ChessBoard = ChessBoard::new_fen(&fen).map_err(|e| format!("invalid fen {}: {:?}", fen, e))?;
+                board.chess960 |= self.chess960;
+                board
+            },
+            _ => return Err("expected startpos or fen".to_string()),
+        };
+        for value in tokens.iter().skip(moves_index + 1) {
+            let m: Move = board.parse_uci_move(value).ok_or(format!("illegal move {}", value))?;
+            board.make_move(m, false);
+        }
+        self.board = board;
+        Ok(())
+    }
+
+    fn go(&mut self, tokens: &[&str]) {
+        let mut limits: SearchLimits = SearchLimits::default();
+        let mut ponder: bool = false;
+        let mut i: usize = 0;
+        while i < tokens.len() {
+            let value: Option<u64> = tokens.get(i + 1).and_then(|t| t.parse::<i64>().ok()).map(|v| v.max(0) as u64);
+            match tokens[i] {
+                "infinite" => limits.infinite = true,
+                "ponder" => ponder = true,
+                "depth" => limits.depth = value.map(|v| v as u32),
+                "nodes" => limits.nodes = value,
+                "movetime" => limits.movetime = value,
+                "wtime" => limits.wtime = value,
+                "btime" => limits.btime = value,
+                "winc" => limits.winc = value,
+                "binc" => limits.binc = value,
+                "movestogo" => limits.movestogo = value.map(|v| v as u32),
+                // Mate in n is found within 2n - 1 plies
+                "mate" => limits.depth = value.map(|v| (v * 2).saturating_sub(1).max(1) as u32),
+                _ => (),
+            }
+            i += 1;
+        }
+
+        // A bare go searches until stop, like infinite
+        if limits == SearchLimits::default() { limits.infinite = true; }
+
+        // Book moves are played right away, except when the GUI wants to see a search
+        if self.own_book && !ponder && !limits.infinite {
+            if let Some(m) = self.book.as_ref().and_then(|book| book.random_move(&self.board)) {
+                println!("info string book move");
+                println!("bestmove {}", m.to_uci());
+                return;
+            }
+        }
+
+        // A ponder search runs until ponderhit or stop, only then the clock starts
+        let search_limits: SearchLimits = if ponder {
+            self.ponder_limits = Some(limits);
+            SearchLimits::infinite()
+        } else {
+            self.ponder_limits = None;
+            limits
+        };
+        self.infinite = search_limits.infinite;
+        self.stop = StopFlag::new();
+
+        let board: ChessBoard = self.board;
+        let tt: Arc<Mutex<TranspositionTable>> = Arc::clone(&self.tt);
+        let stop: StopFlag = self.stop.clone();
+        let tablebase: Option<Arc<Tablebase>> = self.tablebase.clone();
+        self.search = Some(thread::spawn(move || {
+            let mut tt = tt.lock().unwrap();
+            let result = search_with_tablebase(&board, search_limits, &mut tt, &stop, tablebase.as_deref(), &mut |info| println!("{}", info_line(info)));
+            // The GUI expects no bestmove before it says stop during an infinite search
+            if search_limits.infinite {
+                while !stop.is_stopped() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+            match (result.best_move, result.pv.get(1)) {
+                (Some(m), Some(ponder)) => println!("bestmove {} ponder {}", m.to_uci(), ponder.to_uci()),
+                (Some(m), None) => println!("bestmove {}", m.to_uci()),
+                (None, _) => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    fn ponderhit(&mut self) {
+        let limits: SearchLimits = match self.ponder_limits.take() {
+            Some(limits) => limits,
+            None => return,
+        };
+        self.infinite = limits.infinite;
+        if limits.infinite { return; }
+        let stop: StopFlag = self.stop.clone();
+        match TimeManager::new(&limits, self.board.white_turn).soft_limit() {
+            Some(time) => {
+                thread::spawn(move || {
+                    thread::sleep(time);
+                    stop.stop();
+                });
+            },
+            // Depth and node limits can not be applied to a search that is already running
+            None => stop.stop(),
+        }
+    }
+
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_index: usize = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        let name: String = tokens.iter().take(value_index).skip(1).copied().collect::<Vec<&str>>().join(" ");
+        let value: String = tokens.iter().skip(value_index + 1).copied().collect::<Vec<&str>>().join(" ");
+        self.wait();
+        match name.to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(mb) if (1..=MAX_HASH_MB).contains(&mb) => {
+                    self.hash_mb = mb;
+                    self.tt.lock().unwrap().resize(self.hash_mb);
+                },
+                _ => println!("info string invalid hash size {}", value),
+            },
+            "clear hash" => self.tt.lock().unwrap().clear(),
+            "uci_chess960" => {
+                self.chess960 = value == "true";
+                self.board.chess960 = self.chess960;
+            },
+            "ponder" => (),
+            "ownbook" => self.own_book = value == "true",
+            "book file" => {
+                self.book = None;
+                if value.is_empty() || value == "<empty>" { return; }
+                match PolyglotBook::open(&value) {
+                    Ok(book) => self.book = Some(book),
+                    Err(error) => println!("info string can not open book {}: {:?}", value, error),
+                }
+            },
+            "syzygypath" => {
+                self.tablebase = None;
+                if value.is_empty() || value == "<empty>" { return; }
+                match Tablebase::open(&value) {
+                    Ok(tablebase) => {
+                        println!("info string found {} tablebases up to {} pieces", tablebase.len(), tablebase.max_pieces());
+                        self.tablebase = Some(Arc::new(tablebase));
+                    },
+                    Err(error) => println!("info string can not open tablebases {}: {:?}", value, error),
+                }
+            },
+            _ => println!("info string unknown option {}", name),
+        }
+    }
+}
+
+fn info_line(info: &SearchInfo) -> String {
+    let score: String = match info.score {
+        Score::Centipawns(value) => format!("cp {}", value),
+        Score::Mate(moves) => format!("mate {}", moves),
+        Score::Tablebase(plies) => format!("cp {}", plies.signum() * (TB_WIN_CENTIPAWNS - plies.abs())),
+    };
+    let millis: u64 = info.time.as_millis() as u64;
+    let nps: u64 = info.nodes * 1000 / millis.max(1);
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+    format!("info depth {} score {} nodes {} nps {} time {} hashfull {} tbhits {} pv {}",
+        info.depth, score, info.nodes, nps, millis, info.hashfull, info.tbhits, pv.join(" "))
+}
+
+fn main() {
+    let mut engine: Engine = Engine::new();
+    for line in io::stdin().lock().lines() {
+        let line: String = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !engine.handle(line.trim()) { return; }
+    }
+    // Input ended without quit, finish what was asked but do not wait forever
+    if engine.infinite { engine.stop.stop(); }
+    engine.wait();
+}
@@ -0,0 +1,433 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::game::{Game, GameError, Outcome, Termination};
+use crate::{Color, Move};
+
+// Where the clock gets the time from, only differences between two calls matter
+pub trait TimeSource: Send {
+    fn now(&self) -> Duration;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RealTime {
+    start: Instant,
+}
+
+impl Default for RealTime {
+    fn default() -> RealTime {
+        RealTime::new()
+    }
+}
+
+impl RealTime {
+    pub fn new() -> RealTime {
+        RealTime { start: Instant::now() }
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// Only moves when told to, for tests and replaying games. Clones all share the same time
+#[derive(Clone, Debug, Default)]
+pub struct ManualTime {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualTime {
+    pub fn new() -> ManualTime {
+        ManualTime::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Increment {
+    None,
+    // Added after every move
+    Fischer(Duration),
+    // The time used is given back after the move, but never more than the delay
+    Bronstein(Duration),
+    // The clock only starts counting down after the delay
+    Delay(Duration),
+}
+
+// Time for a number of moves, or for the rest of the game when moves is None
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Stage {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub increment: Increment,
+}
+
+// The stages are played in order. When the last one has a number of moves it starts over every time it is done
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimeControl {
+    pub stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    pub fn new(stages: Vec<Stage>) -> TimeControl {
+        TimeControl { stages }
+    }
+
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, increment: Increment::None }])
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, increment: Increment::Fischer(increment) }])
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, increment: Increment::Bronstein(delay) }])
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, increment: Increment::Delay(delay) }])
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ClockError {
+    NotRunning,
+    // The color whose time ran out
+    Flagged(Color),
+}
+
+fn index(color: Color) -> usize {
+    if color == Color::Black { 1 } else { 0 }
+}
+
+fn other(color: Color) -> Color {
+    if color == Color::White { Color::Black } else { Color::White }
+}
+
+pub struct Clock {
+    control: TimeControl,
+    time_source: Box<dyn TimeSource>,
+    // White first, the time left at the start of the running turn
+    remaining: [Duration; 2],
+    moves: [u32; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u32; 2],
+    turn: Color,
+    running: bool,
+    // When the running turn started, or how much of it was used before a pause
+    turn_start: Duration,
+    paused_elapsed: Duration,
+    flagged: Option<Color>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock::with_time_source(control, Box::new(RealTime::new()))
+    }
+
+    pub fn with_time_source(control: TimeControl, time_source: Box<dyn TimeSource>) -> Clock {
+        let time: Duration = control.stages.first().map_or(Duration::ZERO, |stage| stage.time);
+        Clock {
+            control,
+            time_source,
+            remaining: [time, time],
+            moves: [0, 0],
+            stage: [0, 0],
+            moves_in_stage: [0, 0],
+            turn: Color::White,
+            running: false,
+            turn_start: Duration::ZERO,
+            paused_elapsed: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    // Starts the time of the given color
+    pub fn start(&mut self, color: Color) {
+        self.turn = color;
+        self.running = true;
+        self.turn_start = self.time_source.now();
+        self.paused_elapsed = Duration::ZERO;
+    }
+
+    pub fn pause(&mut self) {
+        if !self.running { return; }
+        self.paused_elapsed = self.elapsed();
+        self.running = false;
+    }
+
+    pub fn resume(&mut self) {
+        if self.running || self.flagged.is_some() { return; }
+        self.running = true;
+        self.turn_start = self.time_source.now().saturating_sub(self.paused_elapsed);
+        self.paused_elapsed = Duration::ZERO;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    // Moves finished by the color
+    pub fn moves(&self, color: Color) -> u32 {
+        self.moves[index(color)]
+    }
+
+    // The time used in the running turn so far
+    pub fn elapsed(&self) -> Duration {
+        if self.running { self.time_source.now().saturating_sub(self.turn_start) } else { self.paused_elapsed }
+    }
+
+    fn increment(&self, color: Color) -> Increment {
+        self.control.stages.get(self.stage[index(color)]).map_or(Increment::None, |stage| stage.increment)
+    }
+
+    // What the clock of the color shows, counting the running turn. A delay is not counted until it is used up
+    pub fn remaining(&self, color: Color) -> Duration {
+        let remaining: Duration = self.remaining[index(color)];
+        if color != self.turn { return remaining; }
+        let used: Duration = match self.increment(color) {
+            Increment::Delay(delay) => self.elapsed().saturating_sub(delay),
+            _ => self.elapsed(),
+        };
+        remaining.saturating_sub(used)
+    }
+
+    // The color whose time has run out
+    pub fn flag(&self) -> Option<Color> {
+        if self.flagged.is_some() { return self.flagged; }
+        if (self.running || self.elapsed() > Duration::ZERO) && self.remaining(self.turn).is_zero() { Some(self.turn) } else { None }
+    }
+
+    // Ends the turn of the side to move and starts the opponent's
+    pub fn press(&mut self) -> Result<(), ClockError> {
+        if let Some(color) = self.flag() {
+            self.flagged = Some(color);
+            self.running = false;
+            return Err(ClockError::Flagged(color));
+        }
+        if !self.running { return Err(ClockError::NotRunning); }
+        let color: Color = self.turn;
+        let i: usize = index(color);
+        let elapsed: Duration = self.elapsed();
+        self.remaining[i] = self.remaining(color) + match self.increment(color) {
+            Increment::Fischer(increment) => increment,
+            Increment::Bronstein(delay) => elapsed.min(delay),
+            _ => Duration::ZERO,
+        };
+        self.moves[i] += 1;
+        self.moves_in_stage[i] += 1;
+        if let Some(stage) = self.control.stages.get(self.stage[i]) {
+            if stage.moves == Some(self.moves_in_stage[i]) {
+                // After the last stage it starts over
+                if self.stage[i] + 1 < self.control.stages.len() { self.stage[i] += 1; }
+                self.moves_in_stage[i] = 0;
+                self.remaining[i] += self.control.stages[self.stage[i]].time;
+            }
+        }
+        self.start(other(color));
+        Ok(())
+    }
+}
+
+// A game played with a clock. The clock starts with the first move, when a flag falls the opponent wins
+// unless it has too little material to ever mate, then it is a draw
+pub struct TimedGame {
+    pub game: Game,
+    pub clock: Clock,
+    started: bool,
+}
+
+impl TimedGame {
+    pub fn new(game: Game, clock: Clock) -> TimedGame {
+        TimedGame { game, clock, started: false }
+    }
+
+    pub fn play(&mut self, m: Move) -> Result<(), GameError> {
+        if self.check_flag().is_some() { return Err(GameError::GameOver); }
+        // The clock is only touched once the move is known to be playable
+        self.game.check_playable()?;
+        if !self.game.last_board().is_move_legal(&m) { return Err(GameError::IllegalMove(m.to_uci())); }
+        let color: Color = if self.game.last_board().white_turn { Color::White } else { Color::Black };
+        if !self.started {
+            self.clock.start(color);
+            self.started = true;
+        } else {
+            self.clock.resume();
+        }
+        self.game.play(m)?;
+        if self.clock.press().is_err() {
+            // Can only happen if the flag fell between checking and playing, the move still counts
+            self.check_flag();
+        }
+        if self.game.is_over() { self.clock.pause(); }
+        Ok(())
+    }
+
+    // Ends the game if the flag of the side to move has fallen, should be called regularly while waiting for a move
+    pub fn check_flag(&mut self) -> Option<Outcome> {
+        if self.game.is_over() { return None; }
+        let color: Color = self.clock.flag()?;
+        let winner: Color = if self.game.last_board().has_insufficient_material(other(color)) { Color::None } else { other(color) };
+        let outcome: Outcome = Outcome { winner, termination: Termination::TimeForfeit };
+        self.clock.pause();
+        self.game.set_outcome(Some(outcome));
+        Some(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChessBoard;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn clock(control: TimeControl) -> (Clock, ManualTime) {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_time_source(control, Box::new(time.clone()));
+        clock.start(Color::White);
+        (clock, time)
+    }
+
+    #[test]
+    fn increments_and_delays() {
+        let (mut c, time) = clock(TimeControl::sudden_death(secs(60)));
+        time.advance(secs(10));
+        assert_eq!(c.remaining(Color::White), secs(50));
+        assert_eq!(c.remaining(Color::Black), secs(60));
+        c.press().unwrap();
+        assert_eq!(c.turn(), Color::Black);
+        time.advance(secs(5));
+        c.press().unwrap();
+        assert_eq!((c.remaining(Color::White), c.remaining(Color::Black)), (secs(50), secs(55)));
+        assert_eq!(c.moves(Color::Black), 1);
+
+        let (mut c, time) = clock(TimeControl::fischer(secs(60), secs(2)));
+        time.advance(secs(10));
+        c.press().unwrap();
+        assert_eq!(c.remaining(Color::White), secs(52));
+
+        // Bronstein gives back at most the delay, the simple delay only starts counting after it
+        let (mut c, time) = clock(TimeControl::bronstein(secs(60), secs(5)));
+        time.advance(secs(3));
+        assert_eq!(c.remaining(Color::White), secs(57));
+        c.press().unwrap();
+        assert_eq!(c.remaining(Color::White), secs(60));
+        time.advance(secs(8));
+        c.press().unwrap();
+        assert_eq!(c.remaining(Color::Black), secs(57));
+
+        let (mut c, time) = clock(TimeControl::simple_delay(secs(60), secs(5)));
+        time.advance(secs(3));
+        assert_eq!(c.remaining(Color::White), secs(60));
+        c.press().unwrap();
+        time.advance(secs(8));
+        assert_eq!(c.remaining(Color::Black), secs(57));
+    }
+
+    #[test]
+    fn stages() {
+        // Two moves in 10 seconds, then 5 seconds for the rest, with 1 second per move all along
+        let control = TimeControl::new(vec![
+            Stage { moves: Some(2), time: secs(10), increment: Increment::Fischer(secs(1)) },
+            Stage { moves: None, time: secs(5), increment: Increment::Fischer(secs(1)) }]);
+        let (mut c, time) = clock(control);
+        for _ in 0..4 {
+            time.advance(secs(2));
+            c.press().unwrap();
+        }
+        assert_eq!(c.remaining(Color::White), secs(10 - 4 + 2 + 5));
+        assert_eq!(c.remaining(Color::Black), secs(13));
+
+        // A repeating stage adds its time again
+        let control = TimeControl::new(vec![Stage { moves: Some(1), time: secs(10), increment: Increment::None }]);
+        let (mut c, time) = clock(control);
+        time.advance(secs(4));
+        c.press().unwrap();
+        assert_eq!(c.remaining(Color::White), secs(16));
+    }
+
+    #[test]
+    fn flag_and_pause() {
+        let (mut c, time) = clock(TimeControl::simple_delay(secs(10), secs(2)));
+        time.advance(secs(11));
+        assert_eq!(c.flag(), None);
+        c.pause();
+        time.advance(secs(100));
+        c.resume();
+        assert_eq!(c.elapsed(), secs(11));
+        time.advance(secs(1));
+        assert_eq!(c.flag(), Some(Color::White));
+        assert_eq!(c.press(), Err(ClockError::Flagged(Color::White)));
+        assert!(!c.is_running());
+
+        let mut c = Clock::with_time_source(TimeControl::sudden_death(secs(1)), Box::new(ManualTime::new()));
+        assert_eq!(c.press(), Err(ClockError::NotRunning));
+    }
+
+    #[test]
+    fn timed_game() {
+        let time = ManualTime::new();
+        let clock = Clock::with_time_source(TimeControl::sudden_death(secs(60)), Box::new(time.clone()));
+        let mut game = TimedGame::new(Game::new(), clock);
+        // Nothing counts before the first move
+        time.advance(secs(100));
+        assert_eq!(game.check_flag(), None);
+        game.play(game.game.last_board().parse_san("e4").unwrap()).unwrap();
+        time.advance(secs(61));
+        let outcome = game.check_flag();
+        assert_eq!(outcome, Some(Outcome { winner: Color::White, termination: Termination::TimeForfeit }));
+        assert_eq!(game.game.result(), "1-0");
+        assert_eq!(game.play(Move::from_uci("e7e5").unwrap()), Err(GameError::GameOver));
+
+        // An illegal first move does not start the clock
+        let time = ManualTime::new();
+        let clock = Clock::with_time_source(TimeControl::sudden_death(secs(60)), Box::new(time.clone()));
+        let mut game = TimedGame::new(Game::new(), clock);
+        assert_eq!(game.play(Move::from_uci("e2e5").unwrap()), Err(GameError::IllegalMove("e2e5".to_string())));
+        assert!(!game.clock.is_running());
+        time.advance(secs(30));
+        game.play(Move::from_uci("e2e4").unwrap()).unwrap();
+        assert_eq!(game.clock.remaining(Color::White), secs(60));
+        // Nor does it run the clock while paused
+        game.clock.pause();
+        time.advance(secs(5));
+        assert!(game.play(Move::from_uci("e7e4").unwrap()).is_err());
+        assert!(!game.clock.is_running());
+        assert_eq!(game.clock.remaining(Color::Black), secs(60));
+
+        // A lone king can not win on time
+        let time = ManualTime::new();
+        let clock = Clock::with_time_source(TimeControl::sudden_death(secs(60)), Box::new(time.clone()));
+        let board = ChessBoard::new_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let mut game = TimedGame::new(Game::from_position(&board), clock);
+        game.play(board.parse_san("e4").unwrap()).unwrap();
+        game.play(Move::from_uci("e8d7").unwrap()).unwrap();
+        time.advance(secs(61));
+        assert_eq!(game.check_flag(), Some(Outcome { winner: Color::None, termination: Termination::TimeForfeit }));
+        assert_eq!(game.game.result(), "1/2-1/2");
+    }
+}
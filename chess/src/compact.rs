@@ -0,0 +1,195 @@
+use crate::{square_from_i32, BitBoard, ChessBoard, Color, DecodeError, Move, Piece, Square};
+
+const PROMOTIONS: [Piece; 5] = [Piece::None, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+// The nibble of each piece, white first. The two codes after them are rooks that can still castle
+const PIECES: [Piece; 6] = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King];
+const WHITE_CASTLING_ROOK: u8 = 12;
+const BLACK_CASTLING_ROOK: u8 = 13;
+
+const BLACK_TO_MOVE: u8 = 1;
+const CHESS960: u8 = 2;
+const EN_PASSANT: u8 = 4;
+// Set when the en passant square is on the sixth rank, the file is in the three bits above
+const EN_PASSANT_SIXTH_RANK: u8 = 8;
+const EN_PASSANT_FILE_SHIFT: u8 = 4;
+const EN_PASSANT_FILE: u8 = 7 << EN_PASSANT_FILE_SHIFT;
+const HEADER_BYTES: usize = 13;
+
+impl Move {
+    // From square in the low 6 bits, then the to square and the promotion (0 none, 1 knight, 2 bishop, 3 rook, 4 queen)
+    pub fn to_u16(&self) -> u16 {
+        let promotion: usize = PROMOTIONS.iter().position(|p| *p == self.promotion).unwrap_or(0);
+        self.from.to_i32() as u16 | (self.to.to_i32() as u16) << 6 | (promotion as u16) << 12
+    }
+
+    pub fn from_u16(value: u16) -> Option<Move> {
+        let promotion: Piece = *PROMOTIONS.get((value >> 12) as usize)?;
+        let from: Square = square_from_i32((value & 63) as i32);
+        let to: Square = square_from_i32((value >> 6 & 63) as i32);
+        Some(Move::new_promotion(from, to, promotion))
+    }
+}
+
+impl ChessBoard {
+    // A flags byte, the clocks and the occupied squares, followed by a nibble for every piece from A1 to H8.
+    // At most 29 bytes, clocks above 65535 are stored as 65535
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags: u8 = 0;
+        if !self.white_turn { flags |= BLACK_TO_MOVE; }
+        if self.chess960 { flags |= CHESS960; }
+        if let Some(square) = self.en_passant {
+            flags |= EN_PASSANT | (square.column as u8) << EN_PASSANT_FILE_SHIFT;
+            if square.row == 5 { flags |= EN_PASSANT_SIXTH_RANK; }
+        }
+        let occupied: BitBoard = BitBoard::new(self.white_pieces().value | self.black_pieces().value);
+
+        let mut bytes: Vec<u8> = vec![flags];
+        bytes.extend_from_slice(&(self.halfmove_clock.min(u16::MAX as u32) as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.fullmove_number.min(u16::MAX as u32) as u16).to_le_bytes());
+        bytes.extend_from_slice(&occupied.value.to_le_bytes());
+        for (i, square) in occupied.to_squares().into_iter().enumerate() {
+            let white: bool = self.get_square_color(square) == Color::White;
+            let piece: Piece = self.get_square_piece(square);
+            let code: u8 = if piece == Piece::Rook && self.castling_rights.get_square(square) {
+                if white { WHITE_CASTLING_ROOK } else { BLACK_CASTLING_ROOK }
+            } else {
+                PIECES.iter().position(|p| *p == piece).unwrap() as u8 + if white { 0 } else { 6 }
+            };
+            if i % 2 == 0 {
+                bytes.push(code);
+            } else {
+                *bytes.last_mut().unwrap() |= code << 4;
+            }
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<ChessBoard, DecodeError> {
+        if bytes.len() < HEADER_BYTES { return Err(DecodeError::TooShort); }
+        let flags: u8 = bytes[0];
+        if flags & !(BLACK_TO_MOVE | CHESS960 | EN_PASSANT | EN_PASSANT_SIXTH_RANK | EN_PASSANT_FILE) != 0 ||
+           (flags & EN_PASSANT == 0 && flags & (EN_PASSANT_SIXTH_RANK | EN_PASSANT_FILE) != 0) {
+            return Err(DecodeError::InvalidFlags);
+        }
+        let occupied: BitBoard = BitBoard::new(u64::from_le_bytes(bytes[5..HEADER_BYTES].try_into().unwrap()));
+        let length: usize = HEADER_BYTES + (occupied.count() as usize).div_ceil(2);
+        if bytes.len() < length { return Err(DecodeError::TooShort); }
+        if bytes.len() > length { return Err(DecodeError::TooLong); }
+
+        let mut board: ChessBoard = ChessBoard::empty();
+        board.white_turn = flags & BLACK_TO_MOVE == 0;
+        board.chess960 = flags & CHESS960 != 0;
+        if flags & EN_PASSANT != 0 {
+            let row: i32 = if flags & EN_PASSANT_SIXTH_RANK != 0 { 5 } else { 2 };
+            board.en_passant = Some(Square::new(row, (flags >> EN_PASSANT_FILE_SHIFT) as i32));
+        }
+        board.halfmove_clock = u16::from_le_bytes([bytes[1], bytes[2]]) as u32;
+        board.fullmove_number = u16::from_le_bytes([bytes[3], bytes[4]]) as u32;
+        for (i, square) in occupied.to_squares().into_iter().enumerate() {
+            let code: u8 = bytes[HEADER_BYTES + i / 2] >> (i % 2 * 4) & 15;
+            let (piece, color): (Piece, Color) = match code {
+                0..=5 => (PIECES[code as usize], Color::White),
+                6..=11 => (PIECES[code as usize - 6], Color::Black),
+                // A rook can only castle from its own back rank
+                WHITE_CASTLING_ROOK if square.row == 0 => {
+                    board.castling_rights.set_square(square);
+                    (Piece::Rook, Color::White)
+                },
+                BLACK_CASTLING_ROOK if square.row == 7 => {
+                    board.castling_rights.set_square(square);
+                    (Piece::Rook, Color::Black)
+                },
+                _ => return Err(DecodeError::InvalidPiece(square)),
+            };
+            board.set_square_piece(square, piece, color);
+        }
+        // The bytes may come from anywhere, so only legal positions are accepted
+        match board.validate().first() {
+            Some(error) => Err(DecodeError::InvalidPosition(*error)),
+            None => Ok(board),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PositionError;
+
+    #[test]
+    fn move_round_trip() {
+        let b = ChessBoard::new_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        for m in b.generate_moves() {
+            assert_eq!(Move::from_u16(m.to_u16()), Some(m));
+        }
+        assert_eq!(Move::from_uci("e7e8q").unwrap().to_u16(), 52 | 60 << 6 | 4 << 12);
+        assert_eq!(Move::from_u16(5 << 12), None);
+    }
+
+    #[test]
+    fn position_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 12",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "4k3/8/8/8/8/8/8/4K3 b - - 99 70000"] {
+            let b = ChessBoard::new_fen(fen).unwrap();
+            let bytes = b.encode();
+            assert!(bytes.len() <= 29);
+            assert_eq!(ChessBoard::decode(&bytes).unwrap().to_fen(), fen.replace("70000", "65535"));
+        }
+        let start = ChessBoard::new().encode();
+        assert_eq!(start.len(), 29);
+        assert_eq!(ChessBoard::new_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap().encode().len(), 14);
+
+        // Chess960 castling rights are kept with the rooks
+        let b = ChessBoard::new_chess960(518 + 1);
+        let decoded = ChessBoard::decode(&b.encode()).unwrap();
+        assert!(decoded.chess960);
+        assert_eq!(decoded.to_shredder_fen(), b.to_shredder_fen());
+        assert_eq!(decoded.castling_rights, b.castling_rights);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let start = ChessBoard::new().encode();
+        assert_eq!(ChessBoard::decode(&start[..12]).err(), Some(DecodeError::TooShort));
+        assert_eq!(ChessBoard::decode(&start[..20]).err(), Some(DecodeError::TooShort));
+        let mut bytes = start.clone();
+        bytes.push(0);
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::TooLong));
+        let mut bytes = start.clone();
+        bytes[0] = 16;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidFlags));
+        let mut bytes = start.clone();
+        bytes[13] = 0xFE;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidPiece(Square::new(0, 0))));
+
+        // Bit 7 would put the en passant square past the H file
+        let mut bytes = start.clone();
+        bytes[0] = 0xF4;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidFlags));
+        let mut bytes = start.clone();
+        bytes[0] = EN_PASSANT_SIXTH_RANK;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidFlags));
+
+        // Castling rooks off their own back rank
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        let mut bytes = b.encode();
+        bytes[13] = BLACK_CASTLING_ROOK | (bytes[13] & 0xF0);
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidPiece(Square::new(0, 0))));
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+        let mut bytes = b.encode();
+        bytes[13] = (bytes[13] & 0x0F) | WHITE_CASTLING_ROOK << 4;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidPiece(Square::new(1, 0))));
+
+        // Well formed bytes that do not make a legal position
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut bytes = b.encode();
+        bytes[13] = 11 | 11 << 4;
+        assert_eq!(ChessBoard::decode(&bytes).err(), Some(DecodeError::InvalidPosition(PositionError::MissingKing(Color::White))));
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/4K3 b - e3 0 1").unwrap();
+        assert_eq!(ChessBoard::decode(&b.encode()).err(), Some(DecodeError::InvalidPosition(PositionError::InvalidEnPassant(Square::new(2, 4)))));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{ChessBoard, Move};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EpdError {
+    InvalidFen(String),
+    InvalidOperation(String),
+    UnterminatedString(String),
+    // The opcode and the move that is not legal in the position
+    IllegalMove(String, String),
+}
+
+// One line of an EPD file: a position and its operations, kept in the order they were written
+#[derive(Clone)]
+pub struct EpdRecord {
+    pub board: ChessBoard,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdRecord {
+    pub fn new(board: &ChessBoard) -> EpdRecord {
+        EpdRecord { board: *board, operations: Vec::new() }
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(o, _)| o == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn set_operation(&mut self, opcode: &str, operands: &[&str]) {
+        let operands: Vec<String> = operands.iter().map(|s| s.to_string()).collect();
+        match self.operations.iter_mut().find(|(o, _)| o == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(|id| id.as_str())
+    }
+
+    // The moves of the bm operation
+    pub fn best_moves(&self) -> Result<Vec<Move>, EpdError> {
+        self.moves("bm")
+    }
+
+    // The moves of the am operation
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, EpdError> {
+        self.moves("am")
+    }
+
+    // Moves are written in SAN, but UCI is accepted too
+    fn moves(&self, opcode: &str) -> Result<Vec<Move>, EpdError> {
+        self.operation(opcode).unwrap_or(&[]).iter()
+            .map(|value| self.board.parse_san(value)
+                .or_else(|| self.board.parse_uci_move(value))
+                .ok_or(EpdError::IllegalMove(opcode.to_string(), value.clone())))
+            .collect()
+    }
+
+    // A move solves the position if it is one of the best moves and none of the moves to avoid
+    pub fn is_solved(&self, m: &Move) -> Result<bool, EpdError> {
+        let best: Vec<Move> = self.best_moves()?;
+        let avoid: Vec<Move> = self.avoid_moves()?;
+        Ok((best.is_empty() || best.contains(m)) && !avoid.contains(m))
+    }
+
+    pub fn to_epd(&self) -> String {
+        let fen: String = self.board.to_fen();
+        let mut epd: String = fen.split_whitespace().take(4).collect::<Vec<&str>>().join(" ");
+        for (opcode, operands) in &self.operations {
+            epd.push(' ');
+            epd.push_str(opcode);
+            for operand in operands {
+                epd.push(' ');
+                if is_string_opcode(opcode) || operand.is_empty() || operand.contains([' ', ';', '"']) {
+                    epd.push_str(&format!("\"{}\"", operand.replace('"', "\\\"")));
+                } else {
+                    epd.push_str(operand);
+                }
+            }
+            epd.push(';');
+        }
+        epd
+    }
+}
+
+// id and the comments c0 to c9 hold text
+fn is_string_opcode(opcode: &str) -> bool {
+    opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode.ends_with(|c: char| c.is_ascii_digit()))
+}
+
+// Reads one EPD line. The position has the first four FEN fields, the clocks come from the hmvc and fmvn operations.
+// A full FEN with both clocks is accepted as well
+pub fn parse_epd(line: &str) -> Result<EpdRecord, EpdError> {
+    let line: &str = line.trim();
+    let mut fields: Vec<&str> = Vec::new();
+    let mut rest: &str = line;
+    while fields.len() < 4 {
+        rest = rest.trim_start();
+        let end: usize = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 { return Err(EpdError::InvalidFen(line.to_string())); }
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    // Opcodes start with a letter, so two numbers after the fields are the clocks of a FEN
+    let tokens: Vec<&str> = rest.split_whitespace().take(2).collect();
+    if tokens.len() == 2 && tokens.iter().all(|t| t.parse::<u32>().is_ok()) {
+        fields.extend(tokens);
+        for _ in 0..2 {
+            rest = rest.trim_start();
+            rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+        }
+    }
+    let mut board: ChessBoard = ChessBoard::new_fen(&fields.join(" ")).map_err(|_| EpdError::InvalidFen(fields.join(" ")))?;
+
+    let mut record: EpdRecord = EpdRecord { board, operations: Vec::new() };
+    let mut chars = rest.chars().peekable();
+    loop {
+        let mut words: Vec<String> = Vec::new();
+        let mut word: String = String::new();
+        let mut ended: bool = false;
+        while let Some(c) = chars.next() {
+            match c {
+                ';' => { ended = true; break; },
+                '"' => {
+                    let mut text: String = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => if let Some(escaped) = chars.next() { text.push(escaped) },
+                            Some('"') => break,
+                            Some(c) => text.push(c),
+                            None => return Err(EpdError::UnterminatedString(text)),
+                        }
+                    }
+                    words.push(text);
+                },
+                c if c.is_whitespace() => if !word.is_empty() { words.push(std::mem::take(&mut word)) },
+                c => word.push(c),
+            }
+        }
+        if !word.is_empty() { words.push(word); }
+        if words.is_empty() {
+            if ended { continue; }
+            break;
+        }
+        let opcode: String = words.remove(0);
+        if !opcode.starts_with(|c: char| c.is_ascii_alphabetic()) || !opcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(EpdError::InvalidOperation(opcode));
+        }
+        record.operations.push((opcode, words));
+    }
+
+    for (opcode, operands) in &record.operations {
+        let value: Option<u32> = operands.first().and_then(|v| v.parse::<u32>().ok());
+        match opcode.as_str() {
+            "hmvc" => board.halfmove_clock = value.ok_or(EpdError::InvalidOperation(opcode.clone()))?,
+            "fmvn" => board.fullmove_number = value.ok_or(EpdError::InvalidOperation(opcode.clone()))?,
+            _ => (),
+        }
+    }
+    record.board = board;
+    Ok(record)
+}
+
+// Every position in the text, one per line. Empty lines and lines starting with # are skipped
+pub fn parse_epd_file(text: &str) -> Result<Vec<EpdRecord>, EpdError> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_epd)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Square;
+
+    const WAC_1: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn parse_operations() {
+        let record = parse_epd(WAC_1).unwrap();
+        assert_eq!(record.board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(record.id(), Some("WAC.001"));
+        assert_eq!(record.operation("bm"), Some(&["Qg6".to_string()][..]));
+        let qg6 = Move::new(Square::new(2, 6), Square::new(5, 6));
+        assert_eq!(record.best_moves(), Ok(vec![qg6]));
+        assert_eq!(record.is_solved(&qg6), Ok(true));
+        assert_eq!(record.is_solved(&Move::new(Square::new(2, 6), Square::new(1, 6))), Ok(false));
+        assert_eq!(record.to_epd(), WAC_1);
+
+        // Several moves, clocks, a comment with a semicolon and a FEN with clocks
+        let record = parse_epd("4k3/8/8/8/8/8/4P3/4K3 w - - 3 20 am Kd1 e2e3; bm e4 Kf2; c0 \"a; b\"; hmvc 7; noop;").unwrap();
+        assert_eq!(record.board.halfmove_clock, 7);
+        assert_eq!(record.board.fullmove_number, 20);
+        assert_eq!(record.operation("c0"), Some(&["a; b".to_string()][..]));
+        assert_eq!(record.operation("noop"), Some(&[][..]));
+        assert_eq!(record.avoid_moves().unwrap().len(), 2);
+        assert_eq!(record.best_moves().unwrap().len(), 2);
+        let e4 = record.board.parse_san("e4").unwrap();
+        assert_eq!(record.is_solved(&e4), Ok(true));
+        assert_eq!(parse_epd(&record.to_epd()).unwrap().to_epd(), record.to_epd());
+
+        let mut record = EpdRecord::new(&ChessBoard::new());
+        record.set_operation("id", &["start"]);
+        record.set_operation("id", &["start position"]);
+        assert_eq!(record.to_epd(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"start position\";");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_epd("8/8/8 w - -").err(), Some(EpdError::InvalidFen("8/8/8 w - -".to_string())));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K3 w -").err(), Some(EpdError::InvalidFen("4k3/8/8/8/8/8/8/4K3 w -".to_string())));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"open").err(), Some(EpdError::UnterminatedString("open".to_string())));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K3 w - - 1bm Kd1;").err(), Some(EpdError::InvalidOperation("1bm".to_string())));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K3 w - - hmvc x;").err(), Some(EpdError::InvalidOperation("hmvc".to_string())));
+        let record = parse_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Ke3;").unwrap();
+        assert_eq!(record.best_moves(), Err(EpdError::IllegalMove("bm".to_string(), "Ke3".to_string())));
+
+        let records = parse_epd_file(&format!("# WAC\n\n{}\n{}\n", WAC_1, "4k3/8/8/8/8/8/8/4K3 b - - id \"2\";")).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id(), Some("2"));
+        assert!(parse_epd_file("x\n").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{BitBoard, ChessBoard, Move, Piece, Square};
+
+// Tables are written the way a board diagram looks from white's side, so A8 comes first and H1 last.
+// Index them with the Piece enum (King, Queen, Rook, Bishop, Knight, Pawn).
+
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0];
+
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     20,  20,  20,  20,  20,  20,  20,  20,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0];
+
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50];
+
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20];
+
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0];
+
+const ROOK_EG: [i32; 64] = [
+     10,  10,  10,  10,  10,  10,  10,  10,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0];
+
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20];
+
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20];
+
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EvalParams {
+    pub material_mg: [i32; 6],
+    pub material_eg: [i32; 6],
+    pub tables_mg: [[i32; 64]; 6],
+    pub tables_eg: [[i32; 64]; 6],
+    pub mobility_mg: [i32; 6],
+    pub mobility_eg: [i32; 6],
+    pub king_shield: i32,
+    pub king_zone_attack: i32,
+    pub doubled_pawn_mg: i32,
+    pub doubled_pawn_eg: i32,
+    pub isolated_pawn_mg: i32,
+    pub isolated_pawn_eg: i32,
+    // Indexed by how far the pawn has advanced, 0 being its own back rank
+    pub passed_pawn_mg: [i32; 8],
+    pub passed_pawn_eg: [i32; 8],
+}
+
+pub const DEFAULT_PARAMS: EvalParams = EvalParams {
+    material_mg: [0, 1025, 477, 365, 337, 82],
+    material_eg: [0, 936, 512, 297, 281, 94],
+    tables_mg: [KING_MG, QUEEN, ROOK_MG, BISHOP, KNIGHT, PAWN_MG],
+    tables_eg: [KING_EG, QUEEN, ROOK_EG, BISHOP, KNIGHT, PAWN_EG],
+    mobility_mg: [0, 1, 2, 5, 4, 0],
+    mobility_eg: [0, 2, 4, 5, 4, 0],
+    king_shield: 10,
+    king_zone_attack: -8,
+    doubled_pawn_mg: -10,
+    doubled_pawn_eg: -20,
+    isolated_pawn_mg: -10,
+    isolated_pawn_eg: -15,
+    passed_pawn_mg: [0, 5, 10, 15, 25, 40, 60, 0],
+    passed_pawn_eg: [0, 10, 20, 35, 55, 80, 120, 0],
+};
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        DEFAULT_PARAMS
+    }
+}
+
+// Game phase weights, a full set of pieces adds up to 24
+const PHASE: [i32; 6] = [0, 4, 2, 1, 1, 0];
+const MAX_PHASE: i32 = 24;
+
+// Plain material values for exchanges and move ordering, the king is worth more than everything else together
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => 20000,
+        Piece::None => 0,
+        _ => DEFAULT_PARAMS.material_mg[piece as usize],
+    }
+}
+
+pub fn evaluate(board: &ChessBoard) -> i32 {
+    evaluate_with(board, &DEFAULT_PARAMS)
+}
+
+pub fn evaluate_with(board: &ChessBoard, params: &EvalParams) -> i32 {
+    let (white_mg, white_eg): (i32, i32) = side_terms(board, params, true);
+    let (black_mg, black_eg): (i32, i32) = side_terms(board, params, false);
+    let mg: i32 = white_mg - black_mg;
+    let eg: i32 = white_eg - black_eg;
+
+    let phase: i32 = phase(board);
+    let score: i32 = (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+    if board.white_turn { score } else { -score }
+}
+
+pub fn phase(board: &ChessBoard) -> i32 {
+    let phase: i32 =
+        (board.white_queen.count() + board.black_queen.count()) as i32 * PHASE[Piece::Queen as usize] +
+        (board.white_rook.count() + board.black_rook.count()) as i32 * PHASE[Piece::Rook as usize] +
+        (board.white_bishop.count() + board.black_bishop.count()) as i32 * PHASE[Piece::Bishop as usize] +
+        (board.white_knight.count() + board.black_knight.count()) as i32 * PHASE[Piece::Knight as usize];
+    phase.min(MAX_PHASE)
+}
+
+fn table_index(square: Square, white: bool) -> usize {
+    let row: i32 = if white { 7 - square.row } else { square.row };
+    (row * 8 + square.column) as usize
+}
+
+fn side_terms(board: &ChessBoard, params: &EvalParams, white: bool) -> (i32, i32) {
+    let mut mg: i32 = 0;
+    let mut eg: i32 = 0;
+
+    let pieces: [(Piece, BitBoard, usize); 6] = if white {[
+        (Piece::King, board.white_king, 0),
+        (Piece::Queen, board.white_queen, board.generate_white_queen_moves().len()),
+        (Piece::Rook, board.white_rook, board.generate_white_rook_moves().len()),
+        (Piece::Bishop, board.white_bishop, board.generate_white_bishop_moves().len()),
+        (Piece::Knight, board.white_knight, board.generate_white_knight_moves().len()),
+        (Piece::Pawn, board.white_pawn, 0)]
+    } else {[
+        (Piece::King, board.black_king, 0),
+        (Piece::Queen, board.black_queen, board.generate_black_queen_moves().len()),
+        (Piece::Rook, board.black_rook, board.generate_black_rook_moves().len()),
+        (Piece::Bishop, board.black_bishop, board.generate_black_bishop_moves().len()),
+        (Piece::Knight, board.black_knight, board.generate_black_knight_moves().len()),
+        (Piece::Pawn, board.black_pawn, 0)]
+    };
+
+    for (piece, bb, mobility) in pieces {
+        let p: usize = piece as usize;
+        for square in bb.to_squares() {
+            mg += params.material_mg[p] + params.tables_mg[p][table_index(square, white)];
+            eg += params.material_eg[p] + params.tables_eg[p][table_index(square, white)];
+        }
+        mg += params.mobility_mg[p] * mobility as i32;
+        eg += params.mobility_eg[p] * mobility as i32;
+    }
+
+    mg += king_safety(board, params, white);
+
+    let (pawns_mg, pawns_eg): (i32, i32) = pawn_structure(board, params, white);
+    (mg + pawns_mg, eg + pawns_eg)
+}
+
+fn king_safety(board: &ChessBoard, params: &EvalParams, white: bool) -> i32 {
+    let (king, pawns): (BitBoard, BitBoard) = if white {
+        (board.white_king, board.white_pawn)
+    } else {
+        (board.black_king, board.black_pawn)
+    };
+    let king: Square = match king.to_squares().first() {
+        Some(square) => *square,
+        None => return 0,
+    };
+    let mut score: i32 = 0;
+
+    // Pawns right in front of a king that stayed home shield it, one step further up they count half
+    let forward: i32 = if white { 1 } else { -1 };
+    if (white && king.row <= 1) || (!white && king.row >= 6) {
+        for c in (king.column - 1)..(king.column + 2) {
+            if pawns.get_square(Square::new(king.row + forward, c)) {
+                score += params.king_shield;
+            } else if pawns.get_square(Square::new(king.row + 2 * forward, c)) {
+                score += params.king_shield / 2;
+            }
+        }
+    }
+
+    let mut zone: BitBoard = BitBoard::new(0);
+    for r in -1..2 {
+        for c in -1..2 {
+            let square: Square = Square::new(king.row + r, king.column + c);
+            if square.is_valid() { zone.set_square(square); }
+        }
+    }
+    let attacks: Vec<Move> = if white {
+        [board.generate_black_queen_moves(), board.generate_black_rook_moves(),
+         board.generate_black_bishop_moves(), board.generate_black_knight_moves()].concat()
+    } else {
+        [board.generate_white_queen_moves(), board.generate_white_rook_moves(),
+         board.generate_white_bishop_moves(), board.generate_white_knight_moves()].concat()
+    };
+    let attacked: usize = attacks.iter().filter(|m| zone.get_square(m.to)).count();
+    score + params.king_zone_attack * attacked as i32
+}
+
+fn pawn_structure(board: &ChessBoard, params: &EvalParams, white: bool) -> (i32, i32) {
+    let (own, other): (BitBoard, BitBoard) = if white {
+        (board.white_pawn, board.black_pawn)
+    } else {
+        (board.black_pawn, board.white_pawn)
+    };
+    let mut mg: i32 = 0;
+    let mut eg: i32 = 0;
+
+    let files: Vec<u32> = (0..8).map(|c| BitBoard::new(own.value & (0x0101010101010101 << c)).count()).collect();
+    for c in 0..8 {
+        if files[c] > 1 {
+            mg += params.doubled_pawn_mg * (files[c] - 1) as i32;
+            eg += params.doubled_pawn_eg * (files[c] - 1) as i32;
+        }
+        let left: u32 = if c > 0 { files[c - 1] } else { 0 };
+        let right: u32 = if c < 7 { files[c + 1] } else { 0 };
+        if files[c] > 0 && left == 0 && right == 0 {
+            mg += params.isolated_pawn_mg * files[c] as i32;
+            eg += params.isolated_pawn_eg * files[c] as i32;
+        }
+    }
+
+    for pawn in own.to_squares() {
+        let passed: bool = !other.to_squares().iter().any(|s| {
+            (s.column - pawn.column).abs() <= 1 && if white { s.row > pawn.row } else { s.row < pawn.row }
+        });
+        if passed {
+            let advanced: usize = if white { pawn.row as usize } else { 7 - pawn.row as usize };
+            mg += params.passed_pawn_mg[advanced];
+            eg += params.passed_pawn_eg[advanced];
+        }
+    }
+    (mg, eg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Swaps colors and flips the board upside down, which must not change the evaluation
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let board: Vec<String> = fields[0].split('/').rev().map(|row| {
+            row.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+        }).collect();
+        let turn: &str = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} - - 0 1", board.join("/"), turn)
+    }
+
+    #[test]
+    fn symmetric_positions() {
+        assert_eq!(evaluate(&ChessBoard::new()), 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R b - - 1 8"] {
+            let b = ChessBoard::new_fen(fen).unwrap();
+            let mirrored = ChessBoard::new_fen(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&b), evaluate(&mirrored), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn side_to_move_perspective() {
+        let white = ChessBoard::new_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let black = ChessBoard::new_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert!(evaluate(&white) > 800);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+
+    #[test]
+    fn pawn_structure_terms() {
+        let passed = ChessBoard::new_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = ChessBoard::new_fen("4k3/3p4/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let doubled = ChessBoard::new_fen("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1").unwrap();
+        let connected = ChessBoard::new_fen("4k3/8/8/8/8/8/2PP4/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&passed) > evaluate(&blocked) + DEFAULT_PARAMS.material_eg[Piece::Pawn as usize]);
+        assert!(evaluate(&connected) > evaluate(&doubled));
+    }
+
+    #[test]
+    fn tunable_params() {
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let params = EvalParams { material_mg: [0; 6], material_eg: [0; 6], ..EvalParams::default() };
+        assert!(evaluate_with(&b, &params) < evaluate(&b) - 800);
+        assert_eq!(phase(&b), 4);
+        assert_eq!(phase(&ChessBoard::new()), 24);
+    }
+}
@@ -0,0 +1,490 @@
+use std::time::SystemTime;
+
+use crate::pgn::{PgnError, PgnGame};
+use crate::{ChessBoard, Color, Move};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GameError {
+    IllegalMove(String),
+    GameOver,
+    // Moves can only be played after the last one, not while looking at an earlier ply
+    NotAtEnd,
+    InvalidPly(usize),
+    NoDrawOffer,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoveRule,
+    Repetition,
+    Resignation,
+    Agreement,
+    TimeForfeit,
+    // A result without a reason, like one read from a PGN file
+    Other,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    // Color::None for a draw
+    pub winner: Color,
+    pub termination: Termination,
+}
+
+impl Outcome {
+    pub fn result(&self) -> &'static str {
+        match self.winner {
+            Color::White => "1-0",
+            Color::Black => "0-1",
+            Color::None => "1/2-1/2",
+        }
+    }
+}
+
+// A game from its start position: the moves with their SAN and the time they were played, the position after
+// every ply, how it ended and its PGN tags
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "GameData", into = "GameData"))]
+pub struct Game {
+    boards: Vec<ChessBoard>,
+    moves: Vec<Move>,
+    san: Vec<String>,
+    timestamps: Vec<Option<SystemTime>>,
+    ply: usize,
+    outcome: Option<Outcome>,
+    draw_offer: Option<Color>,
+    pub tags: Vec<(String, String)>,
+}
+
+// What a game is stored as with serde. Reading it back replays the moves, so only legal games are accepted
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GameData {
+    start: ChessBoard,
+    moves: Vec<Move>,
+    timestamps: Vec<Option<SystemTime>>,
+    ply: usize,
+    outcome: Option<Outcome>,
+    draw_offer: Option<Color>,
+    tags: Vec<(String, String)>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Game> for GameData {
+    fn from(game: Game) -> GameData {
+        GameData {
+            start: game.boards[0],
+            moves: game.moves,
+            timestamps: game.timestamps,
+            ply: game.ply,
+            outcome: game.outcome,
+            draw_offer: game.draw_offer,
+            tags: game.tags,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GameData> for Game {
+    type Error = String;
+
+    fn try_from(data: GameData) -> Result<Game, String> {
+        if data.timestamps.len() != data.moves.len() {
+            return Err(format!("{} timestamps for {} moves", data.timestamps.len(), data.moves.len()));
+        }
+        if data.ply > data.moves.len() { return Err(format!("ply {} after the last move", data.ply)); }
+        let mut game: Game = Game::from_position(&data.start);
+        for (m, time) in data.moves.into_iter().zip(data.timestamps) {
+            if !game.last_board().generate_moves().contains(&m) { return Err(format!("illegal move {}", m.to_uci())); }
+            game.push(m, time);
+        }
+        game.ply = data.ply;
+        // Replaying already found any outcome the final position explains, only the others are taken from the data
+        if game.outcome.is_none() {
+            game.outcome = data.outcome.filter(|outcome| matches!(outcome.termination,
+                Termination::Resignation | Termination::Agreement | Termination::TimeForfeit | Termination::Other));
+        }
+        game.draw_offer = data.draw_offer;
+        game.tags = data.tags;
+        Ok(game)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::from_position(&ChessBoard::new())
+    }
+
+    pub fn from_position(start: &ChessBoard) -> Game {
+        let mut game: Game = Game {
+            boards: vec![*start],
+            moves: Vec::new(),
+            san: Vec::new(),
+            timestamps: Vec::new(),
+            ply: 0,
+            outcome: None,
+            draw_offer: None,
+            tags: PgnGame::new().tags,
+        };
+        game.outcome = game.position_outcome();
+        game
+    }
+
+    // Replays the moves of a PGN game. A result the moves do not explain is kept with Termination::Other
+    pub fn from_pgn(pgn: &PgnGame) -> Result<Game, PgnError> {
+        let mut game: Game = Game::from_position(&pgn.start_position()?);
+        for m in pgn.board_moves()? {
+            game.push(m, None);
+        }
+        game.tags = pgn.tags.iter().filter(|(name, _)| name != "SetUp" && name != "FEN").cloned().collect();
+        if game.outcome.is_none() {
+            let winner: Option<Color> = match pgn.result.as_str() {
+                "1-0" => Some(Color::White),
+                "0-1" => Some(Color::Black),
+                "1/2-1/2" => Some(Color::None),
+                _ => None,
+            };
+            game.outcome = winner.map(|winner| Outcome { winner, termination: Termination::Other });
+        }
+        Ok(game)
+    }
+
+    pub fn start(&self) -> &ChessBoard {
+        &self.boards[0]
+    }
+
+    // The position at the current ply
+    pub fn board(&self) -> &ChessBoard {
+        &self.boards[self.ply]
+    }
+
+    pub fn last_board(&self) -> &ChessBoard {
+        self.boards.last().unwrap()
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn san_moves(&self) -> &[String] {
+        &self.san
+    }
+
+    // When each move was played, None for moves read from PGN
+    pub fn timestamps(&self) -> &[Option<SystemTime>] {
+        &self.timestamps
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    pub fn result(&self) -> &'static str {
+        self.outcome.map_or("*", |outcome| outcome.result())
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn play(&mut self, m: Move) -> Result<(), GameError> {
+        self.play_at(m, SystemTime::now())
+    }
+
+    pub fn play_at(&mut self, m: Move, time: SystemTime) -> Result<(), GameError> {
+        self.check_playable()?;
+        if !self.last_board().generate_moves().contains(&m) { return Err(GameError::IllegalMove(m.to_uci())); }
+        self.push(m, Some(time));
+        Ok(())
+    }
+
+    // Accepts SAN and UCI
+    pub fn play_str(&mut self, value: &str) -> Result<(), GameError> {
+        self.check_playable()?;
+        let board: &ChessBoard = self.last_board();
+        let m: Move = board.parse_san(value).or_else(|| board.parse_uci_move(value))
+            .ok_or(GameError::IllegalMove(value.to_string()))?;
+        self.play(m)
+    }
+
+    pub(crate) fn check_playable(&self) -> Result<(), GameError> {
+        if self.outcome.is_some() { return Err(GameError::GameOver); }
+        if self.ply != self.moves.len() { return Err(GameError::NotAtEnd); }
+        Ok(())
+    }
+
+    fn push(&mut self, m: Move, time: Option<SystemTime>) {
+        let mut board: ChessBoard = *self.last_board();
+        let color: Color = if board.white_turn { Color::White } else { Color::Black };
+        self.san.push(board.to_san(&m));
+        board.make_move(m, false);
+        self.boards.push(board);
+        self.moves.push(m);
+        self.timestamps.push(time);
+        self.ply = self.moves.len();
+        // Playing on declines a draw offered by the opponent
+        if self.draw_offer.is_some_and(|offer| offer != color) {
+            self.draw_offer = None;
+        }
+        self.outcome = self.position_outcome();
+    }
+
+    // Takes back the last move and ends a resignation or agreed draw too
+    pub fn undo(&mut self) -> Option<Move> {
+        let m: Move = self.moves.pop()?;
+        self.san.pop();
+        self.timestamps.pop();
+        self.boards.pop();
+        self.ply = self.moves.len();
+        self.draw_offer = None;
+        self.outcome = self.position_outcome();
+        Some(m)
+    }
+
+    // Looks at the position after the given number of plies, 0 is the start position
+    pub fn go_to(&mut self, ply: usize) -> Result<(), GameError> {
+        if ply > self.moves.len() { return Err(GameError::InvalidPly(ply)); }
+        self.ply = ply;
+        Ok(())
+    }
+
+    pub fn resign(&mut self, color: Color) -> Result<(), GameError> {
+        if self.outcome.is_some() { return Err(GameError::GameOver); }
+        let winner: Color = if color == Color::White { Color::Black } else { Color::White };
+        self.outcome = Some(Outcome { winner, termination: Termination::Resignation });
+        Ok(())
+    }
+
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), GameError> {
+        if self.outcome.is_some() { return Err(GameError::GameOver); }
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    // The color that offered a draw that is still open
+    pub fn draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    // Only the opponent of the player who offered the draw can accept it
+    pub fn accept_draw(&mut self, color: Color) -> Result<(), GameError> {
+        if self.outcome.is_some() { return Err(GameError::GameOver); }
+        match self.draw_offer {
+            Some(offer) if offer != color => {
+                self.draw_offer = None;
+                self.outcome = Some(Outcome { winner: Color::None, termination: Termination::Agreement });
+                Ok(())
+            },
+            _ => Err(GameError::NoDrawOffer),
+        }
+    }
+
+    pub fn decline_draw(&mut self) -> Result<(), GameError> {
+        self.draw_offer.take().map(|_| ()).ok_or(GameError::NoDrawOffer)
+    }
+
+    // Ends the game with any outcome, for example when a clock runs out
+    pub fn set_outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcome = outcome;
+        self.draw_offer = None;
+    }
+
+    // Mate, stalemate and the draws that end the game by themselves after the last move
+    fn position_outcome(&self) -> Option<Outcome> {
+        let board: &ChessBoard = self.last_board();
+        let draw = |termination: Termination| Some(Outcome { winner: Color::None, termination });
+        if board.is_white_mated() { return Some(Outcome { winner: Color::Black, termination: Termination::Checkmate }); }
+        if board.is_black_mated() { return Some(Outcome { winner: Color::White, termination: Termination::Checkmate }); }
+        if board.is_white_stalemate() || board.is_black_stalemate() { return draw(Termination::Stalemate); }
+        if board.is_insufficient_material() { return draw(Termination::InsufficientMaterial); }
+        if board.halfmove_clock >= 100 { return draw(Termination::FiftyMoveRule); }
+        let key: u64 = board.zobrist_key();
+        // Only positions since the last capture or pawn move can repeat
+        let since: usize = (board.halfmove_clock as usize).min(self.boards.len() - 1);
+        let repetitions: usize = self.boards[self.boards.len() - 1 - since..].iter().filter(|b| b.zobrist_key() == key).count();
+        if repetitions >= 3 { return draw(Termination::Repetition); }
+        None
+    }
+
+    pub fn to_pgn_game(&self) -> PgnGame {
+        let mut pgn: PgnGame = PgnGame::from_moves(self.start(), &self.moves);
+        for (name, value) in self.tags.iter() {
+            pgn.set_tag(name, value);
+        }
+        pgn.result = self.result().to_string();
+        pgn.set_tag("Result", self.result());
+        pgn
+    }
+
+    pub fn to_pgn(&self) -> String {
+        self.to_pgn_game().to_pgn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_pgn;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn play(game: &mut Game, moves: &str) {
+        for value in moves.split_whitespace() {
+            game.play_str(value).unwrap();
+        }
+    }
+
+    #[test]
+    fn play_undo_and_go_to() {
+        let mut game = Game::new();
+        game.set_tag("White", "Morphy");
+        play(&mut game, "e4 e5 Nf3");
+        let m = game.board().parse_san("d6").unwrap();
+        game.play_at(m, UNIX_EPOCH + Duration::from_secs(60)).unwrap();
+        assert_eq!(game.san_moves(), ["e4", "e5", "Nf3", "d6"]);
+        assert_eq!(game.timestamps()[3], Some(UNIX_EPOCH + Duration::from_secs(60)));
+        assert_eq!(game.ply(), 4);
+        assert_eq!(game.play_str("Ke3"), Err(GameError::IllegalMove("Ke3".to_string())));
+
+        game.go_to(1).unwrap();
+        assert_eq!(game.board().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(game.play_str("e5"), Err(GameError::NotAtEnd));
+        assert_eq!(game.go_to(5), Err(GameError::InvalidPly(5)));
+        assert_eq!(game.undo(), Some(m));
+        assert_eq!(game.ply(), 3);
+        assert_eq!(game.last_board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        assert!(game.to_pgn().contains("[White \"Morphy\"]"));
+        assert!(game.to_pgn().ends_with("\n1. e4 e5 2. Nf3 *\n"));
+    }
+
+    #[test]
+    fn outcomes() {
+        let mut game = Game::new();
+        play(&mut game, "f3 e5 g4 Qh4#");
+        assert_eq!(game.outcome(), Some(Outcome { winner: Color::Black, termination: Termination::Checkmate }));
+        assert_eq!(game.play_str("a3"), Err(GameError::GameOver));
+        assert!(game.to_pgn().ends_with("2. g4 Qh4# 0-1\n"));
+        game.undo();
+        assert!(!game.is_over());
+
+        let mut game = Game::new();
+        play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1");
+        assert!(!game.is_over());
+        play(&mut game, "Ng8");
+        assert_eq!(game.outcome().map(|o| o.termination), Some(Termination::Repetition));
+        assert_eq!(game.result(), "1/2-1/2");
+
+        let game = Game::from_position(&ChessBoard::new_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(game.outcome().map(|o| o.termination), Some(Termination::Stalemate));
+
+        let mut game = Game::new();
+        assert_eq!(game.resign(Color::White), Ok(()));
+        assert_eq!(game.result(), "0-1");
+        assert_eq!(game.resign(Color::Black), Err(GameError::GameOver));
+        game.set_outcome(None);
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn draw_offers() {
+        let mut game = Game::new();
+        assert_eq!(game.accept_draw(Color::Black), Err(GameError::NoDrawOffer));
+        game.offer_draw(Color::White).unwrap();
+        play(&mut game, "e4");
+        // The offer stands until black answers it, playing a move declines it
+        assert_eq!(game.draw_offer(), Some(Color::White));
+        assert_eq!(game.accept_draw(Color::White), Err(GameError::NoDrawOffer));
+        play(&mut game, "e5");
+        assert_eq!(game.draw_offer(), None);
+
+        game.offer_draw(Color::White).unwrap();
+        assert_eq!(game.decline_draw(), Ok(()));
+        game.offer_draw(Color::Black).unwrap();
+        assert_eq!(game.accept_draw(Color::White), Ok(()));
+        assert_eq!(game.outcome(), Some(Outcome { winner: Color::None, termination: Termination::Agreement }));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let mut game = Game::new();
+        game.set_tag("White", "Morphy");
+        play(&mut game, "e4 e5 Nf3");
+        game.play_at(game.board().parse_san("d6").unwrap(), UNIX_EPOCH + Duration::from_secs(60)).unwrap();
+        game.offer_draw(Color::White).unwrap();
+        game.go_to(2).unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"moves\":[\"e2e4\",\"e7e5\",\"g1f3\",\"d7d6\"]"));
+        let again: Game = serde_json::from_str(&json).unwrap();
+        assert_eq!(again.moves(), game.moves());
+        assert_eq!(again.san_moves(), game.san_moves());
+        assert_eq!(again.timestamps(), game.timestamps());
+        assert_eq!(again.ply(), 2);
+        assert_eq!(again.board().to_fen(), game.board().to_fen());
+        assert_eq!(again.draw_offer(), Some(Color::White));
+        assert_eq!(again.to_pgn(), game.to_pgn());
+
+        // Data that does not describe a game is an error rather than a broken Game
+        let start = ChessBoard::new().to_fen();
+        let json = |moves: &str, timestamps: &str, ply: usize| format!(
+            "{{\"start\":\"{}\",\"moves\":[{}],\"timestamps\":[{}],\"ply\":{},\"outcome\":null,\"draw_offer\":null,\"tags\":[]}}",
+            start, moves, timestamps, ply);
+        assert!(serde_json::from_str::<Game>(&json("\"e2e4\"", "null", 1)).is_ok());
+        assert!(serde_json::from_str::<Game>(&json("\"e2e4\"", "null", 2)).is_err());
+        assert!(serde_json::from_str::<Game>(&json("\"e2e4\"", "", 1)).is_err());
+        assert!(serde_json::from_str::<Game>(&json("\"e2e5\"", "null", 1)).is_err());
+
+        // Outcomes that follow from the position are worked out again instead of trusted
+        let with_outcome = |moves: &str, timestamps: &str, ply: usize, outcome: &str| json(moves, timestamps, ply)
+            .replace("\"outcome\":null", &format!("\"outcome\":{}", outcome));
+        let mate: Game = serde_json::from_str(&with_outcome("\"e2e4\"", "null", 1,
+            "{\"winner\":\"White\",\"termination\":\"Checkmate\"}")).unwrap();
+        assert_eq!(mate.outcome(), None);
+        let resigned: Game = serde_json::from_str(&with_outcome("\"e2e4\"", "null", 1,
+            "{\"winner\":\"White\",\"termination\":\"Resignation\"}")).unwrap();
+        assert_eq!(resigned.outcome(), Some(Outcome { winner: Color::White, termination: Termination::Resignation }));
+        let fools_mate: Game = serde_json::from_str(&with_outcome("\"f2f3\",\"e7e5\",\"g2g4\",\"d8h4\"", "null,null,null,null", 4,
+            "{\"winner\":\"White\",\"termination\":\"Resignation\"}")).unwrap();
+        assert_eq!(fools_mate.outcome(), Some(Outcome { winner: Color::Black, termination: Termination::Checkmate }));
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let pgn = &parse_pgn("[White \"A\"]\n[Black \"B\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]\n\n40... Kd7 41. e4 1-0\n").unwrap()[0];
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.start().to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40");
+        assert_eq!(game.moves().len(), 2);
+        assert_eq!(game.timestamps(), [None, None]);
+        assert_eq!(game.outcome(), Some(Outcome { winner: Color::White, termination: Termination::Other }));
+        let text = game.to_pgn();
+        assert!(text.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]"));
+        assert!(text.ends_with("40... Kd7 41. e4 1-0\n"));
+        assert_eq!(parse_pgn(&text).unwrap()[0].moves, pgn.moves);
+    }
+}
@@ -3,8 +3,30 @@ pub fn add(left: usize, right: usize) -> usize {
 }
 
 use std::cmp;
-
-#[derive(PartialEq, Eq)]
+use std::fmt;
+
+mod compact;
+pub mod clock;
+pub mod epd;
+pub mod eval;
+pub mod game;
+pub mod movepick;
+pub mod pgn;
+pub mod polyglot;
+pub mod render;
+mod san;
+pub mod search;
+mod see;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod svg;
+pub mod syzygy;
+pub mod timeman;
+pub mod tree;
+pub mod tt;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Piece {
     King, 
     Queen, 
@@ -15,14 +37,15 @@ pub enum Piece {
     None
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     White, 
     Black, 
     None
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Square {
     pub row: i32, 
     pub column: i32
@@ -33,10 +56,6 @@ impl Square {
         Square { row, column }
     }
 
-    pub fn to_string(&self) -> String {
-        ("ABCDEFGH".as_bytes()[self.column as usize] as char).to_string() + &(self.row + 1).to_string()
-    }
-
     pub fn to_i32(&self) -> i32 {
         self.row * 8 + self.column
     }
@@ -46,22 +65,44 @@ impl Square {
     }
 }
 
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", "ABCDEFGH".as_bytes()[self.column as usize] as char, self.row + 1)
+    }
+}
+
 pub fn square_from_string(value: String) -> Square {
     let row: i32 = (value.as_bytes()[1] as char).to_string().parse::<i32>().unwrap() - 1;
     let column: i32 = "ABCDEFGH".chars().position(|c| c == value.as_bytes()[0] as char).unwrap() as i32;
     Square { row, column }
 }
 
+pub fn parse_square(value: &str) -> Option<Square> {
+    let bytes: &[u8] = value.as_bytes();
+    if bytes.len() != 2 { return None; }
+    let column: i32 = "abcdefgh".chars().position(|c| c == (bytes[0] as char).to_ascii_lowercase())? as i32;
+    let row: i32 = (bytes[1] as char).to_digit(10)? as i32 - 1;
+    let square: Square = Square { row, column };
+    if square.is_valid() { Some(square) } else { None }
+}
+
 pub fn square_from_i32(value: i32) -> Square {
     Square { row: value / 8, column: value % 8 }
 }
 
+pub const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
+pub const DARK_SQUARES: u64 = 0xAA55AA55AA55AA55;
+
+fn on_rank(bb: BitBoard, row: i32) -> BitBoard {
+    BitBoard::new(bb.value & (0xFF << (row * 8)))
+}
+
 fn is_bit(value: u64, bit: i32) -> bool {
     ((value >> bit) & 1) == 1
 }
 
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct BitBoard {
     pub value: u64
 }
@@ -73,17 +114,21 @@ impl BitBoard {
 
     pub fn get_square(&self, square: Square) -> bool {
         if square.is_valid() {
-            return self.value >> square.row * 8 + square.column & 1 == 1;
+            return (self.value >> (square.row * 8 + square.column)) & 1 == 1;
         }
         false
     }
 
     pub fn set_square(&mut self, square: Square) {
-        self.value = self.value | (1 << square.to_i32());
+        self.value |= 1 << square.to_i32();
     }
 
     pub fn remove_square(&mut self, square: Square) {
-        self.value = self.value & (!(1 << square.to_i32()));
+        self.value &= !(1 << square.to_i32());
+    }
+
+    pub fn count(&self) -> u32 {
+        self.value.count_ones()
     }
 
     pub fn to_squares(&self) -> Vec<Square> {
@@ -98,18 +143,135 @@ impl BitBoard {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Move {
     pub from: Square,
-    pub to: Square
+    pub to: Square,
+    pub promotion: Piece
 }
 
 impl Move {
     pub fn new(from: Square, to: Square) -> Move {
-        Move { from, to }
+        Move { from, to, promotion: Piece::None }
+    }
+
+    pub fn new_promotion(from: Square, to: Square, promotion: Piece) -> Move {
+        Move { from, to, promotion }
+    }
+
+    // Long algebraic notation as used by UCI, like e2e4 or e7e8q
+    pub fn to_uci(&self) -> String {
+        let mut value: String = format!("{}{}", self.from, self.to).to_lowercase();
+        if self.promotion != Piece::None {
+            value.push(piece_to_char(self.promotion, Color::Black));
+        }
+        value
+    }
+
+    pub fn from_uci(value: &str) -> Option<Move> {
+        if !value.is_ascii() || (value.len() != 4 && value.len() != 5) { return None; }
+        let from: Square = parse_square(&value[0..2])?;
+        let to: Square = parse_square(&value[2..4])?;
+        let promotion: Piece = match value[4..].chars().next() {
+            Some(c) => match piece_from_char(c) {
+                Some((piece, _)) if piece != Piece::King && piece != Piece::Pawn => piece,
+                _ => return None,
+            },
+            None => Piece::None,
+        };
+        Some(Move::new_promotion(from, to, promotion))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MoveFlags {
+    pub capture: bool,
+    pub en_passant: bool,
+    pub castle: bool,
+    pub double_push: bool,
+    pub promotion: bool,
+    pub check: bool,
+}
+
+fn push_pawn_move(vec: &mut Vec<Move>, from: Square, to: Square) {
+    if to.row == 0 || to.row == 7 {
+        for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+            vec.push(Move::new_promotion(from, to, piece));
+        }
+    } else {
+        vec.push(Move::new(from, to));
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    InvalidBoard,
+    InvalidTurn,
+    InvalidCastling,
+    InvalidEnPassant,
+    InvalidClock,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    TooShort,
+    TooLong,
+    InvalidFlags,
+    InvalidPiece(Square),
+    InvalidPosition(PositionError),
+}
+
+fn piece_from_char(c: char) -> Option<(Piece, Color)> {
+    let color: Color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+    let piece: Piece = match c.to_ascii_lowercase() {
+        'p' => Piece::Pawn,
+        'r' => Piece::Rook,
+        'n' => Piece::Knight,
+        'b' => Piece::Bishop,
+        'q' => Piece::Queen,
+        'k' => Piece::King,
+        _ => return None,
+    };
+    Some((piece, color))
+}
+
+fn piece_to_char(piece: Piece, color: Color) -> char {
+    let c: char = match piece {
+        Piece::Pawn => 'p',
+        Piece::Rook => 'r',
+        Piece::Knight => 'n',
+        Piece::Bishop => 'b',
+        Piece::Queen => 'q',
+        Piece::King => 'k',
+        Piece::None => ' ',
+    };
+    if color == Color::White { c.to_ascii_uppercase() } else { c }
+}
+
+// The squares the king and the rook end up on when castling with the given rook
+fn castling_squares(king: Square, rook: Square) -> (Square, Square) {
+    if rook.column > king.column {
+        (Square::new(king.row, 6), Square::new(king.row, 5))
+    } else {
+        (Square::new(king.row, 2), Square::new(king.row, 3))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PositionError {
+    PieceOverlap(Square),
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    PawnOnBackRank(Square),
+    KingsAdjacent,
+    OpponentInCheck,
+    ImpossibleCheck,
+    InvalidCastlingRight(Square),
+    InvalidEnPassant(Square),
+}
+
 #[derive(Copy, Clone)]
 pub struct ChessBoard {
     pub white_pawn: BitBoard,
@@ -124,12 +286,26 @@ pub struct ChessBoard {
     pub black_bishop: BitBoard,
     pub black_queen: BitBoard,
     pub black_king: BitBoard,
+    // Every square a piece has moved from, castling and en passant no longer depend on it
+    #[deprecated(note = "use castling_rights and en_passant instead")]
     pub moved_pieces: BitBoard,
+    pub castling_rights: BitBoard,
+    pub en_passant: Option<Square>,
     pub white_turn: bool,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub chess960: bool,
+}
+
+impl Default for ChessBoard {
+    fn default() -> ChessBoard {
+        ChessBoard::new()
+    }
 }
 
 impl ChessBoard {
 
+    #[allow(deprecated)]
     pub fn new() -> ChessBoard {
         ChessBoard { 
             white_pawn: BitBoard::new((1 << 8) + (1 << 9) + (1 << 10) + (1 << 11) + (1 << 12) + (1 << 13) + (1 << 14) + (1 << 15)), 
@@ -145,41 +321,204 @@ impl ChessBoard {
             black_queen: BitBoard::new(1 << 59),  
             black_king: BitBoard::new(1 << 60), 
             moved_pieces: BitBoard::new(0),
-            white_turn: true, }
+            castling_rights: BitBoard::new((1 << 0) + (1 << 7) + (1 << 56) + (1 << 63)),
+            en_passant: None,
+            white_turn: true,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false, }
     }
 
-    pub fn new_fen(fen: String) -> ChessBoard {
-        let mut board: ChessBoard = ChessBoard::new();
+    #[allow(deprecated)]
+    pub fn empty() -> ChessBoard {
+        ChessBoard { 
+            white_pawn: BitBoard::new(0), 
+            white_rook: BitBoard::new(0), 
+            white_knight: BitBoard::new(0),
+            white_bishop: BitBoard::new(0),
+            white_queen: BitBoard::new(0), 
+            white_king: BitBoard::new(0),  
+            black_pawn: BitBoard::new(0), 
+            black_rook: BitBoard::new(0), 
+            black_knight: BitBoard::new(0),  
+            black_bishop: BitBoard::new(0), 
+            black_queen: BitBoard::new(0),  
+            black_king: BitBoard::new(0), 
+            moved_pieces: BitBoard::new(0),
+            castling_rights: BitBoard::new(0),
+            en_passant: None,
+            white_turn: true,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false, }
+    }
 
+    pub fn new_chess960(index: u32) -> ChessBoard {
+        let mut board: ChessBoard = ChessBoard::empty();
+        let mut back_rank: [Piece; 8] = [Piece::None; 8];
+        let mut n: u32 = index % 960;
+
+        back_rank[(n % 4 * 2 + 1) as usize] = Piece::Bishop;
+        n /= 4;
+        back_rank[(n % 4 * 2) as usize] = Piece::Bishop;
+        n /= 4;
+        let queen: usize = (n % 6) as usize;
+        n /= 6;
+        let knights: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+        let (first, second): (usize, usize) = knights[n as usize];
+
+        let mut free: Vec<usize> = (0..8).filter(|&c| back_rank[c] == Piece::None).collect();
+        back_rank[free.remove(queen)] = Piece::Queen;
+        back_rank[free[first]] = Piece::Knight;
+        back_rank[free[second]] = Piece::Knight;
+        free.remove(second);
+        free.remove(first);
+        back_rank[free[0]] = Piece::Rook;
+        back_rank[free[1]] = Piece::King;
+        back_rank[free[2]] = Piece::Rook;
+
+        for (c, piece) in back_rank.iter().enumerate() {
+            board.set_square_piece(Square::new(0, c as i32), *piece, Color::White);
+            board.set_square_piece(Square::new(1, c as i32), Piece::Pawn, Color::White);
+            board.set_square_piece(Square::new(6, c as i32), Piece::Pawn, Color::Black);
+            board.set_square_piece(Square::new(7, c as i32), *piece, Color::Black);
+        }
+        board.castling_rights = BitBoard::new(board.white_rook.value | board.black_rook.value);
+        board.chess960 = true;
+        board
+    }
 
+    pub fn new_fen(fen: &str) -> Result<ChessBoard, FenError> {
+        let mut board: ChessBoard = ChessBoard::empty();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.is_empty() { return Err(FenError::InvalidBoard); }
+
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != 8 { return Err(FenError::InvalidBoard); }
+        for (i, row) in rows.iter().enumerate() {
+            let r: i32 = 7 - i as i32;
+            let mut c: i32 = 0;
+            for ch in row.chars() {
+                if let Some(skip) = ch.to_digit(10) {
+                    c += skip as i32;
+                } else {
+                    let (piece, color): (Piece, Color) = piece_from_char(ch).ok_or(FenError::InvalidBoard)?;
+                    if c > 7 { return Err(FenError::InvalidBoard); }
+                    board.set_square_piece(Square::new(r, c), piece, color);
+                    c += 1;
+                }
+            }
+            if c != 8 { return Err(FenError::InvalidBoard); }
+        }
 
-        board
+        board.white_turn = match fields.get(1) {
+            None | Some(&"w") => true,
+            Some(&"b") => false,
+            _ => return Err(FenError::InvalidTurn),
+        };
+
+        let castling: &str = fields.get(2).unwrap_or(&"-");
+        if castling != "-" {
+            for ch in castling.chars() {
+                let white: bool = ch.is_ascii_uppercase();
+                let row: i32 = if white { 0 } else { 7 };
+                let (king, rooks): (BitBoard, BitBoard) = if white { 
+                    (on_rank(board.white_king, row), on_rank(board.white_rook, row)) 
+                } else { 
+                    (on_rank(board.black_king, row), on_rank(board.black_rook, row)) 
+                };
+                let king: Square = *king.to_squares().first().ok_or(FenError::InvalidCastling)?;
+                let rook: Option<Square> = match ch.to_ascii_uppercase() {
+                    // X-FEN letters refer to the outermost rook on that side of the king
+                    'K' => rooks.to_squares().into_iter().rfind(|s| s.column > king.column),
+                    'Q' => rooks.to_squares().into_iter().find(|s| s.column < king.column),
+                    'A'..='H' => {
+                        let square: Square = Square::new(row, ch.to_ascii_uppercase() as i32 - 'A' as i32);
+                        if rooks.get_square(square) { Some(square) } else { None }
+                    }
+                    _ => None,
+                };
+                let rook: Square = rook.ok_or(FenError::InvalidCastling)?;
+                board.castling_rights.set_square(rook);
+                if king.column != 4 || (rook.column != 0 && rook.column != 7) {
+                    board.chess960 = true;
+                }
+            }
+        }
+
+        board.en_passant = match fields.get(3) {
+            None | Some(&"-") => None,
+            Some(value) => {
+                let square: Square = parse_square(value).ok_or(FenError::InvalidEnPassant)?;
+                if square.row != 2 && square.row != 5 { return Err(FenError::InvalidEnPassant); }
+                Some(square)
+            }
+        };
+
+        board.halfmove_clock = fields.get(4).map_or(Ok(0), |v| v.parse::<u32>()).map_err(|_| FenError::InvalidClock)?;
+        board.fullmove_number = fields.get(5).map_or(Ok(1), |v| v.parse::<u32>()).map_err(|_| FenError::InvalidClock)?;
+
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(true)
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let mut fen: String = String::new();
+        for r in (0..8).rev() {
+            let mut empty: u32 = 0;
+            for c in 0..8 {
+                let square: Square = Square::new(r, c);
+                let piece: Piece = self.get_square_piece(square);
+                if piece == Piece::None {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 { fen.push_str(&empty.to_string()); }
+                empty = 0;
+                fen.push(piece_to_char(piece, self.get_square_color(square)));
+            }
+            if empty > 0 { fen.push_str(&empty.to_string()); }
+            if r > 0 { fen.push('/'); }
+        }
+
+        fen.push_str(if self.white_turn { " w " } else { " b " });
+
+        let mut castling: String = String::new();
+        for (row, king, rooks) in [(0, self.white_king, self.white_rook), (7, self.black_king, self.black_rook)] {
+            let king: Option<Square> = on_rank(king, row).to_squares().first().copied();
+            let mut rights: Vec<Square> = on_rank(self.castling_rights, row).to_squares();
+            rights.reverse();
+            for rook in rights {
+                let outermost: bool = match king {
+                    Some(k) if rook.column > k.column => on_rank(rooks, row).to_squares().iter().all(|s| s.column <= rook.column),
+                    Some(_) => on_rank(rooks, row).to_squares().iter().all(|s| s.column >= rook.column),
+                    None => false,
+                };
+                let c: char = if shredder || !outermost {
+                    (b'a' + rook.column as u8) as char
+                } else if rook.column > king.unwrap().column { 'k' } else { 'q' };
+                castling.push(if row == 0 { c.to_ascii_uppercase() } else { c });
+            }
+        }
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {} ", square.to_string().to_lowercase())),
+            None => fen.push_str(" - "),
+        }
+        fen.push_str(&format!("{} {}", self.halfmove_clock, self.fullmove_number));
+        fen
     }
 
     pub fn print(&self) {
-        println!("\n   --------------------------------");
-        for r in 0..8 {
-                print!("{} |", 8 - r);
-                for c in 0..8 {
-                let square: Square = Square::new(7 - r, c);
-                if self.white_pawn.get_square(square) { print!(" P "); } 
-                else if self.black_pawn.get_square(square) { print!(" p "); } 
-                else if self.white_rook.get_square(square) { print!(" R "); } 
-                else if self.black_rook.get_square(square) { print!(" r "); } 
-                else if self.white_bishop.get_square(square) { print!(" B "); } 
-                else if self.black_bishop.get_square(square) { print!(" b "); } 
-                else if self.white_knight.get_square(square) { print!(" N "); } 
-                else if self.black_knight.get_square(square) { print!(" n "); } 
-                else if self.white_queen.get_square(square) { print!(" Q "); } 
-                else if self.black_queen.get_square(square) { print!(" q "); } 
-                else if self.white_king.get_square(square) { print!(" K "); } 
-                else if self.black_king.get_square(square) { print!(" k "); } 
-                else { print!("   "); }
-                print!("|");
-            }
-            println!("\n   --------------------------------");
-        }
-        println!("    A   B   C   D   E   F   G   H")
+        println!("{}", self);
     }
 
     pub fn white_pieces(&self) -> BitBoard {
@@ -225,66 +564,255 @@ impl ChessBoard {
         vec.into_iter().filter(|x| self.is_move_valid(x)).collect::<Vec<Move>>() as Vec<Move>
     }
 
-    pub fn is_move_valid(&self, m: &Move) -> bool {
-        if m.from == square_from_string("E1".to_string()) {
-            if m.to == square_from_string("C1".to_string()) && 
-            !self.is_move_valid(&Move::new(
-                square_from_string("E1".to_string()), 
-                square_from_string("D1".to_string()))
-            ) {
-                return false;
+    // Only captures (en passant included) and promotions, for quiescence search
+    pub fn generate_captures(&self) -> Vec<Move> {
+        let mut vec: Vec<Move> = Vec::new();
+        let (pawn, rook, knight, bishop, queen, king, enemy): (BitBoard, BitBoard, BitBoard, BitBoard, BitBoard, BitBoard, BitBoard) = if self.white_turn {
+            (self.white_pawn, self.white_rook, self.white_knight, self.white_bishop, self.white_queen, self.white_king, self.black_pieces())
+        } else {
+            (self.black_pawn, self.black_rook, self.black_knight, self.black_bishop, self.black_queen, self.black_king, self.white_pieces())
+        };
+        let empty: BitBoard = self.empty_squares();
+        let forward: i32 = if self.white_turn { 1 } else { -1 };
+
+        for from in pawn.to_squares() {
+            for c in [-1, 1] {
+                let to: Square = Square::new(from.row + forward, from.column + c);
+                if !to.is_valid() { continue; }
+                if enemy.get_square(to) {
+                    push_pawn_move(&mut vec, from, to);
+                } else if Some(to) == self.en_passant {
+                    vec.push(Move::new(from, to));
+                }
             }
-            if m.to == square_from_string("G1".to_string()) && 
-            !self.is_move_valid(&Move::new(
-                square_from_string("E1".to_string()), 
-                square_from_string("F1".to_string()))
-            ) {
-                return false;
+            // Quiet pushes are only wanted when they promote
+            let to: Square = Square::new(from.row + forward, from.column);
+            if (to.row == 0 || to.row == 7) && empty.get_square(to) {
+                push_pawn_move(&mut vec, from, to);
             }
         }
-        if m.from == square_from_string("E8".to_string()) {
-            if m.to == square_from_string("C8".to_string()) && 
-            !self.is_move_valid(&Move::new(
-                square_from_string("E8".to_string()), 
-                square_from_string("D8".to_string()))
-            ) {
-                return false;
+
+        for from in knight.to_squares() {
+            for (r, c) in [(2, 1), (2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2), (-2, 1), (-2, -1)] {
+                let to: Square = Square::new(from.row + r, from.column + c);
+                if to.is_valid() && enemy.get_square(to) { vec.push(Move::new(from, to)); }
             }
-            if m.to == square_from_string("G8".to_string()) && 
-            !self.is_move_valid(&Move::new(
-                square_from_string("E8".to_string()), 
-                square_from_string("F8".to_string()))
-            ) {
-                return false;
+        }
+        for from in king.to_squares() {
+            for (r, c) in [(1, 1), (1, 0), (1, -1), (0, 1), (0, -1), (-1, 1), (-1, 0), (-1, -1)] {
+                let to: Square = Square::new(from.row + r, from.column + c);
+                if to.is_valid() && enemy.get_square(to) { vec.push(Move::new(from, to)); }
             }
         }
-        let mut clone: ChessBoard = self.clone();
+
+        // Sliders walk each ray until the first occupied square and keep it if it is an enemy piece
+        for (r, c) in [(1, 1), (1, 0), (1, -1), (0, 1), (0, -1), (-1, 1), (-1, 0), (-1, -1)] {
+            let sliders: BitBoard = if r == 0 || c == 0 { BitBoard::new(rook.value | queen.value) } else { BitBoard::new(bishop.value | queen.value) };
+            for from in sliders.to_squares() {
+                let mut to: Square = Square::new(from.row + r, from.column + c);
+                while to.is_valid() && empty.get_square(to) {
+                    to = Square::new(to.row + r, to.column + c);
+                }
+                if to.is_valid() && enemy.get_square(to) { vec.push(Move::new(from, to)); }
+            }
+        }
+
+        vec.into_iter().filter(|x| self.is_move_valid(x)).collect::<Vec<Move>>()
+    }
+
+    // The piece taken by the move, a pawn for en passant and Piece::None if nothing is captured
+    pub fn captured_piece(&self, m: &Move) -> Piece {
+        let enemy: Color = if self.white_turn { Color::Black } else { Color::White };
+        if self.get_square_color(m.to) == enemy {
+            self.get_square_piece(m.to)
+        } else if Some(m.to) == self.en_passant && self.get_square_piece(m.from) == Piece::Pawn {
+            Piece::Pawn
+        } else {
+            Piece::None
+        }
+    }
+
+    pub fn is_capture(&self, m: &Move) -> bool {
+        self.captured_piece(m) != Piece::None
+    }
+
+    // Finds the legal move written in UCI notation, castling may be given as king to rook or as the king's destination
+    pub fn parse_uci_move(&self, value: &str) -> Option<Move> {
+        let m: Move = Move::from_uci(value)?;
+        let moves: Vec<Move> = self.generate_moves();
+        if moves.contains(&m) { return Some(m); }
+        let rook: Square = self.castling_rook(&m)?;
+        moves.into_iter().find(|x| x.from == m.from && self.castling_rook(x) == Some(rook))
+    }
+
+    // Pseudo-legal moves of the type of piece on the square, for the side to move
+    fn generate_piece_moves(&self, from: Square) -> Vec<Move> {
+        let color: Color = if self.white_turn { Color::White } else { Color::Black };
+        if self.get_square_color(from) != color { return Vec::new(); }
+        match (self.white_turn, self.get_square_piece(from)) {
+            (true, Piece::Pawn)    => self.generate_white_pawn_moves(),
+            (true, Piece::Rook)    => self.generate_white_rook_moves(),
+            (true, Piece::Knight)  => self.generate_white_knight_moves(),
+            (true, Piece::Bishop)  => self.generate_white_bishop_moves(),
+            (true, Piece::Queen)   => self.generate_white_queen_moves(),
+            (true, Piece::King)    => self.generate_white_king_moves(),
+            (false, Piece::Pawn)   => self.generate_black_pawn_moves(),
+            (false, Piece::Rook)   => self.generate_black_rook_moves(),
+            (false, Piece::Knight) => self.generate_black_knight_moves(),
+            (false, Piece::Bishop) => self.generate_black_bishop_moves(),
+            (false, Piece::Queen)  => self.generate_black_queen_moves(),
+            (false, Piece::King)   => self.generate_black_king_moves(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Same as looking the move up in generate_moves, but only the moves of the moving piece type are generated
+    pub fn is_move_legal(&self, m: &Move) -> bool {
+        self.generate_piece_moves(m.from).contains(m) && self.is_move_valid(m)
+    }
+
+    // What kind of move it is, the move is expected to be legal
+    pub fn move_flags(&self, m: &Move) -> MoveFlags {
+        let pawn: bool = self.get_square_piece(m.from) == Piece::Pawn;
+        let castle: bool = self.castling_rook(m).is_some();
+        let mut clone: ChessBoard = *self;
+        clone.make_move(*m, false);
+        MoveFlags {
+            capture: !castle && self.is_capture(m),
+            en_passant: pawn && Some(m.to) == self.en_passant,
+            castle,
+            double_push: pawn && (m.to.row - m.from.row).abs() == 2,
+            promotion: m.promotion != Piece::None,
+            check: if clone.white_turn { clone.is_white_checked() } else { clone.is_black_checked() },
+        }
+    }
+
+    // The king and rook destinations of a castling move
+    pub fn castling_destinations(&self, m: &Move) -> Option<(Square, Square)> {
+        self.castling_rook(m).map(|rook| castling_squares(m.from, rook))
+    }
+
+    pub fn is_move_valid(&self, m: &Move) -> bool {
+        if let Some(rook) = self.castling_rook(m) {
+            if !self.is_castling_allowed(m.from, rook) { return false; }
+        }
+        let mut clone: ChessBoard = *self;
         clone.make_move(*m, false);
         (clone.white_turn && !clone.is_black_checked()) || (!clone.white_turn && !clone.is_white_checked())
     }
 
+    fn castling_move(&self, king: Square, rook: Square) -> Move {
+        // Standard chess moves the king two squares, Chess960 uses king-captures-rook
+        if self.chess960 || king.column != 4 {
+            Move::new(king, rook)
+        } else {
+            Move::new(king, castling_squares(king, rook).0)
+        }
+    }
+
+    // Returns the rook to castle with if the move is a castling move
+    pub fn castling_rook(&self, m: &Move) -> Option<Square> {
+        let (king, rooks): (BitBoard, BitBoard) = if self.white_king.get_square(m.from) {
+            (self.white_king, self.white_rook)
+        } else if self.black_king.get_square(m.from) {
+            (self.black_king, self.black_rook)
+        } else {
+            return None;
+        };
+        if !king.get_square(m.from) || m.from.row != m.to.row { return None; }
+        if rooks.get_square(m.to) { return Some(m.to); }
+        if self.chess960 || m.from.column != 4 || (m.to.column != 2 && m.to.column != 6) { return None; }
+
+        let rights: Vec<Square> = on_rank(self.castling_rights, m.from.row).to_squares();
+        if m.to.column == 6 {
+            Some(rights.into_iter().rfind(|s| s.column > 4).unwrap_or(Square::new(m.from.row, 7)))
+        } else {
+            Some(rights.into_iter().find(|s| s.column < 4).unwrap_or(Square::new(m.from.row, 0)))
+        }
+    }
+
+    pub fn is_castling_allowed(&self, king: Square, rook: Square) -> bool {
+        let white: bool = self.white_king.get_square(king);
+        let (own_king, own_rook): (BitBoard, BitBoard) = if white { 
+            (self.white_king, self.white_rook) 
+        } else { 
+            (self.black_king, self.black_rook) 
+        };
+        if !own_king.get_square(king) || !own_rook.get_square(rook) || 
+           !self.castling_rights.get_square(rook) || king.row != rook.row {
+            return false;
+        }
+
+        // Every square the king or the rook passes must be empty, apart from the king and the rook themselves
+        let (king_to, rook_to): (Square, Square) = castling_squares(king, rook);
+        let min: i32 = cmp::min(cmp::min(king.column, rook.column), cmp::min(king_to.column, rook_to.column));
+        let max: i32 = cmp::max(cmp::max(king.column, rook.column), cmp::max(king_to.column, rook_to.column));
+        for c in min..(max + 1) {
+            let square: Square = Square::new(king.row, c);
+            if square != king && square != rook && !self.empty_squares().get_square(square) {
+                return false;
+            }
+        }
+
+        // The king may not castle out of or through check
+        let mut clone: ChessBoard = *self;
+        clone.remove_square_piece(king);
+        for c in cmp::min(king.column, king_to.column)..(cmp::max(king.column, king_to.column) + 1) {
+            if clone.is_square_attacked(Square::new(king.row, c), !white) {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn is_square_attacked(&self, square: Square, by_white: bool) -> bool {
+        let (pawn, rook, knight, bishop, queen, king): (BitBoard, BitBoard, BitBoard, BitBoard, BitBoard, BitBoard) = if by_white {
+            (self.white_pawn, self.white_rook, self.white_knight, self.white_bishop, self.white_queen, self.white_king)
+        } else {
+            (self.black_pawn, self.black_rook, self.black_knight, self.black_bishop, self.black_queen, self.black_king)
+        };
+
+        let pawn_row: i32 = if by_white { square.row - 1 } else { square.row + 1 };
+        if pawn.get_square(Square::new(pawn_row, square.column - 1)) || pawn.get_square(Square::new(pawn_row, square.column + 1)) {
+            return true;
+        }
+        for (r, c) in [(2, 1), (2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2), (-2, 1), (-2, -1)] {
+            if knight.get_square(Square::new(square.row + r, square.column + c)) { return true; }
+        }
+        for (r, c) in [(1, 1), (1, 0), (1, -1), (0, 1), (0, -1), (-1, 1), (-1, 0), (-1, -1)] {
+            if king.get_square(Square::new(square.row + r, square.column + c)) { return true; }
+        }
+
+        let empty: BitBoard = self.empty_squares();
+        for (r, c) in [(1, 1), (1, 0), (1, -1), (0, 1), (0, -1), (-1, 1), (-1, 0), (-1, -1)] {
+            let sliders: u64 = if r == 0 || c == 0 { rook.value | queen.value } else { bishop.value | queen.value };
+            let mut to: Square = Square::new(square.row + r, square.column + c);
+            while to.is_valid() {
+                if is_bit(sliders, to.to_i32()) { return true; }
+                if !empty.get_square(to) { break; }
+                to = Square::new(to.row + r, to.column + c);
+            }
+        }
+        false
+    }
+
     pub fn generate_white_pawn_moves(&self) -> Vec<Move> {
         let mut vec: Vec<Move> = Vec::new();
         for from in self.white_pawn.to_squares() {
             if from.column < 7 && is_bit(self.black_pieces().value, from.to_i32() + 9) {
-                vec.push(Move::new(from, Square::new(from.row+1, from.column+1)));
+                push_pawn_move(&mut vec, from, Square::new(from.row+1, from.column+1));
             }
             if from.column > 0 && is_bit(self.black_pieces().value, from.to_i32() + 7) {
-                vec.push(Move::new(from, Square::new(from.row+1, from.column-1)));
+                push_pawn_move(&mut vec, from, Square::new(from.row+1, from.column-1));
             }
-            if from.row == 4 {
-                if from.column > 0 && self.black_pawn.get_square(square_from_i32(from.to_i32() - 1)) &&
-                !self.moved_pieces.get_square(square_from_i32(from.to_i32() + 7)) {
-                    vec.push(Move::new(from, Square::new(from.row+1, from.column-1)));
-                }
-                if from.column < 7 && self.black_pawn.get_square(square_from_i32(from.to_i32() + 1)) &&
-                !self.moved_pieces.get_square(square_from_i32(from.to_i32() + 7)) {
-                    vec.push(Move::new(from, Square::new(from.row+1, from.column+1)));
+            if let Some(ep) = self.en_passant {
+                if ep.row == from.row + 1 && (ep.column - from.column).abs() == 1 {
+                    vec.push(Move::new(from, ep));
                 }
             }
 
             if is_bit(self.empty_squares().value, from.to_i32() + 8) {
-                vec.push(Move::new(from, Square::new(from.row+1, from.column)));
+                push_pawn_move(&mut vec, from, Square::new(from.row+1, from.column));
             } else { continue; }
             if from.row == 1 && is_bit(self.empty_squares().value, from.to_i32() + 16) {
                 vec.push(Move::new(from, Square::new(from.row+2, from.column)));
@@ -377,21 +905,14 @@ impl ChessBoard {
             }
         }
 
-        if  !self.moved_pieces.get_square(square_from_string("E1".to_string())) &&
-            !self.moved_pieces.get_square(square_from_string("H1".to_string())) &&
-            self.empty_squares().get_square(square_from_string("F1".to_string())) &&
-            self.empty_squares().get_square(square_from_string("G1".to_string())) {
-                vec.push(Move::new(square_from_string("E1".to_string()), square_from_string("G1".to_string())));
+        for king in on_rank(self.white_king, 0).to_squares() {
+            for rook in on_rank(self.castling_rights, 0).to_squares() {
+                if self.is_castling_allowed(king, rook) {
+                    vec.push(self.castling_move(king, rook));
+                }
             }
+        }
 
-        if  !self.moved_pieces.get_square(square_from_string("E1".to_string())) &&
-            !self.moved_pieces.get_square(square_from_string("A1".to_string())) &&
-            self.empty_squares().get_square(square_from_string("B1".to_string())) &&
-            self.empty_squares().get_square(square_from_string("C1".to_string())) &&
-            self.empty_squares().get_square(square_from_string("D1".to_string())) {
-                vec.push(Move::new(square_from_string("E1".to_string()), square_from_string("C1".to_string())));
-            }
-        
         vec
     }
 
@@ -515,24 +1036,19 @@ impl ChessBoard {
     pub fn generate_black_pawn_moves(&self) -> Vec<Move> {
         let mut vec: Vec<Move> = Vec::new();
         for from in self.black_pawn.to_squares() {
-            if from.column < 7 && is_bit(self.white_pieces().value, from.to_i32() - 9) {
-                vec.push(Move::new(from, Square::new(from.row-1, from.column-1)));
+            if from.column > 0 && is_bit(self.white_pieces().value, from.to_i32() - 9) {
+                push_pawn_move(&mut vec, from, Square::new(from.row-1, from.column-1));
             }
-            if from.column > 0 && is_bit(self.white_pieces().value, from.to_i32() - 7) {
-                vec.push(Move::new(from, Square::new(from.row-1, from.column+1)));
+            if from.column < 7 && is_bit(self.white_pieces().value, from.to_i32() - 7) {
+                push_pawn_move(&mut vec, from, Square::new(from.row-1, from.column+1));
             }
-            if from.row == 3 {
-                if from.column > 0 && self.white_pawn.get_square(square_from_i32(from.to_i32() - 1)) &&
-                !self.moved_pieces.get_square(square_from_i32(from.to_i32() - 9)) {
-                    vec.push(Move::new(from, Square::new(from.row-1, from.column-1)));
-                }
-                if from.column < 7 && self.white_pawn.get_square(square_from_i32(from.to_i32() + 1)) &&
-                !self.moved_pieces.get_square(square_from_i32(from.to_i32() - 7)) {
-                    vec.push(Move::new(from, Square::new(from.row-1, from.column+1)));
+            if let Some(ep) = self.en_passant {
+                if ep.row == from.row - 1 && (ep.column - from.column).abs() == 1 {
+                    vec.push(Move::new(from, ep));
                 }
             }
             if is_bit(self.empty_squares().value, from.to_i32() - 8) {
-                vec.push(Move::new(from, Square::new(from.row-1, from.column)));
+                push_pawn_move(&mut vec, from, Square::new(from.row-1, from.column));
             } else { continue; }
             if from.row == 6 && is_bit(self.empty_squares().value, from.to_i32() - 16) {
                 vec.push(Move::new(from, Square::new(from.row-2, from.column)));
@@ -624,21 +1140,14 @@ impl ChessBoard {
                 }
             }
         }
-        if  !self.moved_pieces.get_square(square_from_string("E8".to_string())) &&
-            !self.moved_pieces.get_square(square_from_string("H8".to_string())) &&
-            self.empty_squares().get_square(square_from_string("F8".to_string())) &&
-            self.empty_squares().get_square(square_from_string("G8".to_string())) {
-                vec.push(Move::new(square_from_string("E8".to_string()), square_from_string("G8".to_string())));
+        for king in on_rank(self.black_king, 7).to_squares() {
+            for rook in on_rank(self.castling_rights, 7).to_squares() {
+                if self.is_castling_allowed(king, rook) {
+                    vec.push(self.castling_move(king, rook));
+                }
             }
+        }
 
-        if  !self.moved_pieces.get_square(square_from_string("E8".to_string())) &&
-            !self.moved_pieces.get_square(square_from_string("A8".to_string())) &&
-            self.empty_squares().get_square(square_from_string("B8".to_string())) &&
-            self.empty_squares().get_square(square_from_string("C8".to_string())) &&
-            self.empty_squares().get_square(square_from_string("D8".to_string())) {
-                vec.push(Move::new(square_from_string("E8".to_string()), square_from_string("C8".to_string())));
-            }
-        
         vec
     }
 
@@ -781,8 +1290,58 @@ impl ChessBoard {
         Color::None
     }
 
+    pub fn set_square_piece(&mut self, square: Square, piece: Piece, color: Color) {
+        self.remove_square_piece(square);
+        let bb: &mut BitBoard = match (color, piece) {
+            (Color::White, Piece::Pawn)   => &mut self.white_pawn,
+            (Color::White, Piece::Rook)   => &mut self.white_rook,
+            (Color::White, Piece::Knight) => &mut self.white_knight,
+            (Color::White, Piece::Bishop) => &mut self.white_bishop,
+            (Color::White, Piece::Queen)  => &mut self.white_queen,
+            (Color::White, Piece::King)   => &mut self.white_king,
+            (Color::Black, Piece::Pawn)   => &mut self.black_pawn,
+            (Color::Black, Piece::Rook)   => &mut self.black_rook,
+            (Color::Black, Piece::Knight) => &mut self.black_knight,
+            (Color::Black, Piece::Bishop) => &mut self.black_bishop,
+            (Color::Black, Piece::Queen)  => &mut self.black_queen,
+            (Color::Black, Piece::King)   => &mut self.black_king,
+            _ => return,
+        };
+        bb.set_square(square);
+    }
+
+    pub fn remove_square_piece(&mut self, square: Square) {
+        self.  white_pawn.remove_square(square);
+        self.  white_rook.remove_square(square);
+        self.white_knight.remove_square(square);
+        self.white_bishop.remove_square(square);
+        self. white_queen.remove_square(square);
+        self.  white_king.remove_square(square);
+        self.  black_pawn.remove_square(square);
+        self.  black_rook.remove_square(square);
+        self.black_knight.remove_square(square);
+        self.black_bishop.remove_square(square);
+        self. black_queen.remove_square(square);
+        self.  black_king.remove_square(square);
+    }
+
     pub fn get_legal_moves_from_square(&self, from: Square) -> Vec<Move> {
-        self.generate_moves().into_iter().filter(|m| m.from == from).collect::<Vec<Move>>()
+        self.generate_piece_moves(from).into_iter()
+            .filter(|m| m.from == from && self.is_move_valid(m))
+            .collect::<Vec<Move>>()
+    }
+
+    pub fn get_legal_moves_with_flags(&self, from: Square) -> Vec<(Move, MoveFlags)> {
+        self.get_legal_moves_from_square(from).into_iter().map(|m| (m, self.move_flags(&m))).collect()
+    }
+
+    // The squares the piece can move to, castling is the king's destination in standard chess and the rook in Chess960
+    pub fn legal_destinations(&self, from: Square) -> BitBoard {
+        let mut destinations: BitBoard = BitBoard::new(0);
+        for m in self.get_legal_moves_from_square(from) {
+            destinations.set_square(m.to);
+        }
+        destinations
     }
 
     pub fn make_move_string(&mut self, from: &str, to: &str) {
@@ -791,7 +1350,33 @@ impl ChessBoard {
 
     pub fn make_move(&mut self, _move: Move, validate: bool) {
         if validate && !self.is_move_valid(&_move) { return; }
-        let capture: bool = !self.empty_squares().get_square(_move.to);
+        let en_passant: Option<Square> = self.en_passant;
+        self.en_passant = None;
+        if !self.white_turn { self.fullmove_number += 1; }
+
+        if let Some(rook) = self.castling_rook(&_move) {
+            let color: Color = self.get_square_color(_move.from);
+            if self.get_square_piece(rook) == Piece::Rook && self.get_square_color(rook) == color {
+                let (king_to, rook_to): (Square, Square) = castling_squares(_move.from, rook);
+                self.remove_square_piece(_move.from);
+                self.remove_square_piece(rook);
+                self.set_square_piece(king_to, Piece::King, color);
+                self.set_square_piece(rook_to, Piece::Rook, color);
+                self.castling_rights.value &= !(0xFF << (_move.from.row * 8));
+                self.halfmove_clock += 1;
+                self.white_turn = !self.white_turn;
+                return;
+            }
+        }
+
+        if self.white_pawn.get_square(_move.from) || self.black_pawn.get_square(_move.from) || 
+           !self.empty_squares().get_square(_move.to) {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        let promotion: Piece = if _move.promotion == Piece::None { Piece::Queen } else { _move.promotion };
+
         self.  white_pawn.remove_square(_move.to);
         self.  white_rook.remove_square(_move.to);
         self.white_knight.remove_square(_move.to);
@@ -806,80 +1391,52 @@ impl ChessBoard {
         self.  black_king.remove_square(_move.to);
 
         if self.white_pawn.get_square(_move.from) { 
-            if _move.from.row == 4 {
-                if _move.to.column == _move.from.column + 1 && !capture {
-                    self.black_pawn.remove_square(square_from_i32(_move.from.to_i32() + 1))
-                }
-                if _move.to.column == _move.from.column - 1 && !capture {
-                    self.black_pawn.remove_square(square_from_i32(_move.from.to_i32() - 1))
-                }
+            if en_passant == Some(_move.to) {
+                self.black_pawn.remove_square(Square::new(_move.from.row, _move.to.column));
+            }
+            if _move.to.row == _move.from.row + 2 {
+                self.en_passant = Some(Square::new(_move.from.row + 1, _move.from.column));
             }
 
             if _move.to.row == 7 {
-                self.white_queen.set_square(_move.to);
+                self.set_square_piece(_move.to, promotion, Color::White);
             } else {
                 self.white_pawn.set_square(_move.to); 
             }
-            self.moved_pieces.set_square(_move.from);
         }
         else if self.white_rook.get_square(_move.from) {
             self.white_rook.set_square(_move.to);
-            self.moved_pieces.set_square(_move.from);
         }
         else if self.white_knight.get_square(_move.from) {self.white_knight.set_square(_move.to); }
         else if self.white_bishop.get_square(_move.from) {self.white_bishop.set_square(_move.to); }
         else if self.white_queen.get_square(_move.from) {self.white_queen.set_square(_move.to); }
         else if self.white_king.get_square(_move.from) {
             self.white_king.set_square(_move.to); 
-            if _move.from == square_from_string("E1".to_string()) {
-                if _move.to == square_from_string("C1".to_string()) {
-                    self.white_rook.remove_square(square_from_string("A1".to_string()));
-                    self.white_rook.set_square(square_from_string("D1".to_string()));
-                }
-                if _move.to == square_from_string("G1".to_string()) {
-                    self.white_rook.remove_square(square_from_string("H1".to_string()));
-                    self.white_rook.set_square(square_from_string("F1".to_string()));
-                }
-            }
-            self.moved_pieces.set_square(_move.from);
+            self.castling_rights.value &= !0xFF;
         }
         else if self.black_pawn.get_square(_move.from) { 
-            if _move.from.row == 3 {
-                if _move.to.column == _move.from.column + 1 && !capture {
-                    self.white_pawn.remove_square(square_from_i32(_move.from.to_i32() + 1))
-                }
-                if _move.to.column == _move.from.column - 1 && !capture {
-                    self.white_pawn.remove_square(square_from_i32(_move.from.to_i32() - 1))
-                }
+            if en_passant == Some(_move.to) {
+                self.white_pawn.remove_square(Square::new(_move.from.row, _move.to.column));
+            }
+            if _move.to.row == _move.from.row - 2 {
+                self.en_passant = Some(Square::new(_move.from.row - 1, _move.from.column));
             }
 
             if _move.to.row == 0 {
-                self.black_queen.set_square(_move.to);
+                self.set_square_piece(_move.to, promotion, Color::Black);
             } else {
                 self.black_pawn.set_square(_move.to); 
             }
-            self.moved_pieces.set_square(_move.from);
         }
         else if self.black_rook.get_square(_move.from) {
             self.black_rook.set_square(_move.to);
-            self.moved_pieces.set_square(_move.from); 
         }
         else if self.black_knight.get_square(_move.from) {self.black_knight.set_square(_move.to); }
         else if self.black_bishop.get_square(_move.from) {self.black_bishop.set_square(_move.to); }
         else if self.black_queen.get_square(_move.from) {self.black_queen.set_square(_move.to); }
         else if self.black_king.get_square(_move.from) {
             self.black_king.set_square(_move.to); 
-            if _move.from == square_from_string("E8".to_string()) {
-                if _move.to == square_from_string("C8".to_string()) {
-                    self.black_rook.remove_square(square_from_string("A8".to_string()));
-                    self.black_rook.set_square(square_from_string("D8".to_string()));
-                }
-                if _move.to == square_from_string("G8".to_string()) {
-                    self.black_rook.remove_square(square_from_string("H8".to_string()));
-                    self.black_rook.set_square(square_from_string("F8".to_string()));
-                }
-            }
-            self.moved_pieces.set_square(_move.from);
+            self.castling_rights.value &= !(0xFF << 56);
         }
 
         self.  white_pawn.remove_square(_move.from);
@@ -894,74 +1451,276 @@ impl ChessBoard {
         self.black_bishop.remove_square(_move.from);
         self. black_queen.remove_square(_move.from);
         self.  black_king.remove_square(_move.from);
+
+        self.castling_rights.remove_square(_move.from);
+        self.castling_rights.remove_square(_move.to);
+        #[allow(deprecated)]
+        self.moved_pieces.set_square(_move.from);
         
         self.white_turn = !self.white_turn;
     }
 
     pub fn is_white_checked(&self) -> bool {
+        self.white_king.to_squares().into_iter().any(|s| self.is_square_attacked(s, false))
+    }
+
+    pub fn is_black_checked(&self) -> bool {
+        self.black_king.to_squares().into_iter().any(|s| self.is_square_attacked(s, true))
+    }
+
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 { return 1; }
+        let moves: Vec<Move> = self.generate_moves();
+        if depth == 1 { return moves.len() as u64; }
+        let mut nodes: u64 = 0;
+        for m in moves {
+            let mut clone: ChessBoard = *self;
+            clone.make_move(m, false);
+            nodes += clone.perft(depth - 1);
+        }
+        nodes
+    }
+
+    pub fn zobrist_key(&self) -> u64 {
+        tt::zobrist_key(self)
+    }
+
+    pub fn polyglot_key(&self) -> u64 {
+        polyglot::polyglot_key(self)
+    }
+
+    pub fn is_white_mated(&self) -> bool {
+        self.white_turn && self.is_white_checked() && self.generate_moves().is_empty()
+    }
+
+    pub fn is_black_mated(&self) -> bool {
+        !self.white_turn && self.is_black_checked() && self.generate_moves().is_empty()
+    }
+
+    pub fn is_white_stalemate(&self) -> bool {
+        self.white_turn && !self.is_white_checked() && self.generate_moves().is_empty()
+    }
+
+    pub fn is_black_stalemate(&self) -> bool {
+        !self.white_turn && !self.is_black_checked() && self.generate_moves().is_empty()
+    }
+
+    fn checkers(&self, white_king: bool) -> BitBoard {
         let mut bb: BitBoard = BitBoard::new(0);
-        for m in self.generate_black_rook_moves() {
-            bb.set_square(m.to);
+        let (king, pawns, moves): (BitBoard, BitBoard, Vec<Move>) = if white_king {
+            (self.white_king, self.black_pawn, [
+                self.generate_black_rook_moves(),   self.generate_black_knight_moves(),
+                self.generate_black_bishop_moves(), self.generate_black_queen_moves()].concat())
+        } else {
+            (self.black_king, self.white_pawn, [
+                self.generate_white_rook_moves(),   self.generate_white_knight_moves(),
+                self.generate_white_bishop_moves(), self.generate_white_queen_moves()].concat())
+        };
+        for m in moves {
+            if king.get_square(m.to) { bb.set_square(m.from); }
         }
-        for m in self.generate_black_knight_moves() {
-            bb.set_square(m.to);
+        let direction: i32 = if white_king { -1 } else { 1 };
+        for from in pawns.to_squares() {
+            if king.get_square(Square::new(from.row + direction, from.column - 1)) ||
+               king.get_square(Square::new(from.row + direction, from.column + 1)) {
+                bb.set_square(from);
+            }
         }
-        for m in self.generate_black_bishop_moves() {
-            bb.set_square(m.to);
+        bb
+    }
+
+    pub fn validate(&self) -> Vec<PositionError> {
+        let mut errors: Vec<PositionError> = Vec::new();
+
+        let boards: [BitBoard; 12] = [
+            self.white_pawn, self.white_rook, self.white_knight, self.white_bishop, self.white_queen, self.white_king,
+            self.black_pawn, self.black_rook, self.black_knight, self.black_bishop, self.black_queen, self.black_king];
+        let mut seen: u64 = 0;
+        let mut overlap: u64 = 0;
+        for bb in boards {
+            overlap |= seen & bb.value;
+            seen |= bb.value;
         }
-        for m in self.generate_black_queen_moves() {
-            bb.set_square(m.to);
+        for square in BitBoard::new(overlap).to_squares() {
+            errors.push(PositionError::PieceOverlap(square));
         }
-        for s in self.generate_black_pawn_threats() {
-            bb.set_square(s);
+
+        for (color, king, pawns, pieces) in [
+            (Color::White, self.white_king, self.white_pawn, self.white_pieces()),
+            (Color::Black, self.black_king, self.black_pawn, self.black_pieces())] {
+            if king.count() == 0 { errors.push(PositionError::MissingKing(color)); }
+            if king.count() > 1 { errors.push(PositionError::TooManyKings(color)); }
+            if pawns.count() > 8 { errors.push(PositionError::TooManyPawns(color)); }
+            if pieces.count() > 16 { errors.push(PositionError::TooManyPieces(color)); }
         }
 
-        (bb.value & self.white_king.value) != 0
-    }
+        let back_ranks: u64 = 0xFF | (0xFF << 56);
+        for square in BitBoard::new((self.white_pawn.value | self.black_pawn.value) & back_ranks).to_squares() {
+            errors.push(PositionError::PawnOnBackRank(square));
+        }
 
-    pub fn is_black_checked(&self) -> bool {
-        let mut bb: BitBoard = BitBoard::new(0);
-        for m in self.generate_white_rook_moves() {
-            bb.set_square(m.to);
+        // The remaining checks only make sense with one king of each color
+        if self.white_king.count() != 1 || self.black_king.count() != 1 {
+            return errors;
         }
-        for m in self.generate_white_knight_moves() {
-            bb.set_square(m.to);
+
+        let white_king: Square = self.white_king.to_squares()[0];
+        let black_king: Square = self.black_king.to_squares()[0];
+        if (white_king.row - black_king.row).abs() <= 1 && (white_king.column - black_king.column).abs() <= 1 {
+            errors.push(PositionError::KingsAdjacent);
+        }
+
+        if self.checkers(!self.white_turn).count() > 0 {
+            errors.push(PositionError::OpponentInCheck);
         }
-        for m in self.generate_white_bishop_moves() {
-            bb.set_square(m.to);
+
+        // A double check is always a discovered check, so one of the checkers must be a slider
+        let checkers: BitBoard = self.checkers(self.white_turn);
+        let sliders: u64 = 
+            self.white_rook.value  | self.white_bishop.value | self.white_queen.value |
+            self.black_rook.value  | self.black_bishop.value | self.black_queen.value;
+        if checkers.count() > 2 || (checkers.count() == 2 && checkers.value & sliders == 0) {
+            errors.push(PositionError::ImpossibleCheck);
         }
-        for m in self.generate_white_queen_moves() {
-            bb.set_square(m.to);
+
+        for square in self.castling_rights.to_squares() {
+            let (king, rook): (BitBoard, BitBoard) = match square.row {
+                0 => (self.white_king, self.white_rook),
+                7 => (self.black_king, self.black_rook),
+                _ => { errors.push(PositionError::InvalidCastlingRight(square)); continue; }
+            };
+            let king_square: Square = king.to_squares()[0];
+            if !rook.get_square(square) || king_square.row != square.row || 
+               king_square.column == 0 || king_square.column == 7 {
+                errors.push(PositionError::InvalidCastlingRight(square));
+            }
         }
-        for s in self.generate_white_pawn_threats() {
-            bb.set_square(s);
+
+        if let Some(ep) = self.en_passant {
+            // The pawn that just made a double push must stand in front of the en passant square
+            let (row, pawn, pawns): (i32, Square, BitBoard) = if self.white_turn {
+                (5, Square::new(4, ep.column), self.black_pawn)
+            } else {
+                (2, Square::new(3, ep.column), self.white_pawn)
+            };
+            let origin: Square = Square::new(2 * ep.row - pawn.row, ep.column);
+            if ep.row != row || !pawns.get_square(pawn) || 
+               !self.empty_squares().get_square(ep) || !self.empty_squares().get_square(origin) {
+                errors.push(PositionError::InvalidEnPassant(ep));
+            }
         }
 
-        (bb.value & self.black_king.value) != 0
+        errors
     }
 
-    pub fn is_white_mated(&self) -> bool {
-        self.white_turn && self.is_white_checked() && self.generate_moves().len() == 0
+    pub fn is_insufficient_material(&self) -> bool {
+        let sufficient: u64 =
+            self.white_pawn.value  | self.black_pawn.value |
+            self.white_rook.value  | self.black_rook.value |
+            self.white_queen.value | self.black_queen.value;
+        if sufficient != 0 { return false; }
+
+        let knights: BitBoard = BitBoard::new(self.white_knight.value | self.black_knight.value);
+        let bishops: BitBoard = BitBoard::new(self.white_bishop.value | self.black_bishop.value);
+        if knights.count() + bishops.count() <= 1 { return true; }
+
+        // Any number of bishops can never mate if they all stand on the same square color
+        knights.count() == 0 && (bishops.value & LIGHT_SQUARES == 0 || bishops.value & DARK_SQUARES == 0)
     }
 
-    pub fn is_black_mated(&self) -> bool {
-        !self.white_turn && self.is_black_checked() && self.generate_moves().len() == 0
+    pub fn has_insufficient_material(&self, color: Color) -> bool {
+        let (own, other): (BitBoard, BitBoard) = match color {
+            Color::White => (self.white_pieces(), self.black_pieces()),
+            Color::Black => (self.black_pieces(), self.white_pieces()),
+            Color::None => return self.is_insufficient_material(),
+        };
+        let pawns: u64 = self.white_pawn.value | self.black_pawn.value;
+        let knights: u64 = self.white_knight.value | self.black_knight.value;
+        let bishops: u64 = self.white_bishop.value | self.black_bishop.value;
+        let rooks: u64 = self.white_rook.value | self.black_rook.value;
+        let queens: u64 = self.white_queen.value | self.black_queen.value;
+        let kings: u64 = self.white_king.value | self.black_king.value;
+
+        if own.value & (pawns | rooks | queens) != 0 { return false; }
+
+        // A lone knight can only mate if the opponent has pieces that can block its own king in
+        if own.value & knights != 0 {
+            return own.count() <= 2 && other.value & !kings & !queens == 0;
+        }
+
+        // Bishops of one square color can only mate with help from the opponent's
+        // pawns, knights or bishops of the other color
+        if own.value & bishops != 0 {
+            let same_color: bool = bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0;
+            return same_color && pawns == 0 && knights == 0;
+        }
+
+        true
     }
 
-    pub fn is_white_stalemate(&self) -> bool {
-        self.white_turn && !self.is_white_checked() && self.generate_moves().len() == 0
+}
+
+pub struct PositionBuilder {
+    board: ChessBoard,
+}
+
+impl Default for PositionBuilder {
+    fn default() -> PositionBuilder {
+        PositionBuilder::new()
     }
+}
 
-    pub fn is_black_stalemate(&self) -> bool {
-        !self.white_turn && !self.is_black_checked() && self.generate_moves().len() == 0
+impl PositionBuilder {
+    pub fn new() -> PositionBuilder {
+        PositionBuilder { board: ChessBoard::empty() }
+    }
+
+    pub fn from_board(board: &ChessBoard) -> PositionBuilder {
+        PositionBuilder { board: *board }
+    }
+
+    pub fn put(&mut self, square: Square, piece: Piece, color: Color) -> &mut PositionBuilder {
+        self.board.set_square_piece(square, piece, color);
+        self
     }
 
+    pub fn remove(&mut self, square: Square) -> &mut PositionBuilder {
+        self.board.remove_square_piece(square);
+        self
+    }
+
+    pub fn side_to_move(&mut self, color: Color) -> &mut PositionBuilder {
+        self.board.white_turn = color != Color::Black;
+        self
+    }
+
+    pub fn castling(&mut self, rook: Square) -> &mut PositionBuilder {
+        self.board.castling_rights.set_square(rook);
+        self
+    }
+
+    pub fn remove_castling(&mut self, rook: Square) -> &mut PositionBuilder {
+        self.board.castling_rights.remove_square(rook);
+        self
+    }
+
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut PositionBuilder {
+        self.board.en_passant = square;
+        self
+    }
+
+    pub fn build(&self) -> Result<ChessBoard, Vec<PositionError>> {
+        let errors: Vec<PositionError> = self.board.validate();
+        if errors.is_empty() { Ok(self.board) } else { Err(errors) }
+    }
 }
 
 
 
 
 #[cfg(test)]
+// The tests print squares through to_string, which Square now gets from Display
+#[allow(clippy::to_string_in_format_args)]
 mod tests {
     use super::*;
 
@@ -982,4 +1741,290 @@ mod tests {
         b.print();
         
     }
+
+    fn kings_only() -> ChessBoard {
+        PositionBuilder::new()
+            .put(square_from_string("E1".to_string()), Piece::King, Color::White)
+            .put(square_from_string("E8".to_string()), Piece::King, Color::Black)
+            .build().unwrap()
+    }
+
+    #[test]
+    fn insufficient_material() {
+        assert!(!ChessBoard::new().is_insufficient_material());
+
+        let mut b = kings_only();
+        assert!(b.is_insufficient_material());
+
+        b.white_knight.set_square(square_from_string("C3".to_string()));
+        assert!(b.is_insufficient_material());
+
+        b.black_knight.set_square(square_from_string("C6".to_string()));
+        assert!(!b.is_insufficient_material());
+
+        let mut b = kings_only();
+        b.white_bishop.set_square(square_from_string("C1".to_string()));
+        b.black_bishop.set_square(square_from_string("F8".to_string()));
+        b.black_bishop.set_square(square_from_string("A3".to_string()));
+        assert!(b.is_insufficient_material());
+
+        b.black_bishop.set_square(square_from_string("C8".to_string()));
+        assert!(!b.is_insufficient_material());
+    }
+
+    #[test]
+    fn validate_position() {
+        assert!(ChessBoard::new().validate().is_empty());
+
+        let mut b = ChessBoard::new();
+        b.white_king.set_square(square_from_string("E4".to_string()));
+        b.white_pawn.set_square(square_from_string("A8".to_string()));
+        b.black_queen.set_square(square_from_string("E2".to_string()));
+        let errors = b.validate();
+        assert!(errors.contains(&PositionError::TooManyKings(Color::White)));
+        assert!(errors.contains(&PositionError::PawnOnBackRank(square_from_string("A8".to_string()))));
+        assert!(errors.contains(&PositionError::PieceOverlap(square_from_string("E2".to_string()))));
+        assert!(errors.contains(&PositionError::TooManyPieces(Color::White)));
+
+        let mut b = ChessBoard::new();
+        b.white_rook.remove_square(square_from_string("H1".to_string()));
+        b.en_passant = Some(square_from_string("E3".to_string()));
+        let errors = b.validate();
+        assert_eq!(errors, vec![
+            PositionError::InvalidCastlingRight(square_from_string("H1".to_string())),
+            PositionError::InvalidEnPassant(square_from_string("E3".to_string()))]);
+
+        let mut b = ChessBoard::new();
+        b.make_move_string("E2", "E4");
+        assert!(b.validate().is_empty());
+        assert_eq!(b.en_passant, Some(square_from_string("E3".to_string())));
+    }
+
+    #[test]
+    fn position_builder() {
+        let b = PositionBuilder::new()
+            .put(square_from_string("E1".to_string()), Piece::King, Color::White)
+            .put(square_from_string("E8".to_string()), Piece::King, Color::Black)
+            .put(square_from_string("E2".to_string()), Piece::Rook, Color::White)
+            .side_to_move(Color::Black)
+            .build();
+        assert!(b.is_ok());
+        assert!(b.unwrap().is_black_checked());
+
+        let errors = PositionBuilder::new()
+            .put(square_from_string("E1".to_string()), Piece::King, Color::White)
+            .put(square_from_string("E8".to_string()), Piece::King, Color::Black)
+            .put(square_from_string("E2".to_string()), Piece::Rook, Color::White)
+            .build().err().unwrap();
+        assert_eq!(errors, vec![PositionError::OpponentInCheck]);
+
+        let errors = PositionBuilder::new()
+            .put(square_from_string("E1".to_string()), Piece::King, Color::White)
+            .put(square_from_string("E2".to_string()), Piece::King, Color::Black)
+            .remove(square_from_string("E1".to_string()))
+            .build().err().unwrap();
+        assert_eq!(errors, vec![PositionError::MissingKing(Color::White)]);
+    }
+
+    #[test]
+    fn position_builder_castling_and_en_passant() {
+        let mut builder = PositionBuilder::new();
+        builder
+            .put(square_from_string("E1".to_string()), Piece::King, Color::White)
+            .put(square_from_string("H1".to_string()), Piece::Rook, Color::White)
+            .put(square_from_string("E8".to_string()), Piece::King, Color::Black)
+            .put(square_from_string("D4".to_string()), Piece::Pawn, Color::Black)
+            .put(square_from_string("E4".to_string()), Piece::Pawn, Color::White)
+            .side_to_move(Color::Black)
+            .castling(square_from_string("H1".to_string()))
+            .en_passant(Some(square_from_string("E3".to_string())));
+        let b = builder.build().unwrap();
+        assert!(b.generate_moves().contains(&Move::new(square_from_string("D4".to_string()), square_from_string("E3".to_string()))));
+
+        let errors = builder.castling(square_from_string("A1".to_string())).build().err().unwrap();
+        assert_eq!(errors, vec![PositionError::InvalidCastlingRight(square_from_string("A1".to_string()))]);
+
+        let castle = Move::new(square_from_string("E1".to_string()), square_from_string("G1".to_string()));
+        let b = PositionBuilder::from_board(&b).side_to_move(Color::White).en_passant(None).build().unwrap();
+        assert!(b.generate_moves().contains(&castle));
+        let b = PositionBuilder::from_board(&b).remove_castling(square_from_string("H1".to_string())).build().unwrap();
+        assert!(!b.generate_moves().contains(&castle));
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(ChessBoard::new().to_fen(), start);
+        assert_eq!(ChessBoard::new_fen(start).unwrap().to_fen(), start);
+        assert_eq!(ChessBoard::new().to_shredder_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+
+        let mut b = ChessBoard::new();
+        b.make_move_string("E2", "E4");
+        assert_eq!(b.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        b.make_move_string("G8", "F6");
+        assert_eq!(b.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2");
+
+        assert_eq!(ChessBoard::new_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1").err(), Some(FenError::InvalidBoard));
+        assert_eq!(ChessBoard::new_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").err(), Some(FenError::InvalidTurn));
+        assert_eq!(ChessBoard::new_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1").err(), Some(FenError::InvalidCastling));
+        assert_eq!(ChessBoard::new_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1").err(), Some(FenError::InvalidEnPassant));
+    }
+
+    #[test]
+    fn generate_captures() {
+        for (fen, count) in [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 8),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 1),
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 15)] {
+            let b = ChessBoard::new_fen(fen).unwrap();
+            let captures: Vec<Move> = b.generate_captures();
+            let expected: Vec<Move> = b.generate_moves().into_iter()
+                .filter(|m| b.get_square_piece(m.to) != Piece::None && b.get_square_color(m.to) != b.get_square_color(m.from)
+                    || Some(m.to) == b.en_passant || m.promotion != Piece::None)
+                .collect();
+            assert_eq!(captures.len(), count, "{}", fen);
+            assert_eq!(captures.len(), expected.len(), "{}", fen);
+            assert!(expected.iter().all(|m| captures.contains(m)), "{}", fen);
+        }
+        let b = ChessBoard::new_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        let ep = Move::new(square_from_string("E5".to_string()), square_from_string("F6".to_string()));
+        assert_eq!(b.captured_piece(&ep), Piece::Pawn);
+        assert!(!b.is_capture(&Move::new(square_from_string("E5".to_string()), square_from_string("E6".to_string()))));
+    }
+
+    #[test]
+    fn uci_moves() {
+        let m = Move::from_uci("e7e8q").unwrap();
+        assert_eq!(m, Move::new_promotion(square_from_string("E7".to_string()), square_from_string("E8".to_string()), Piece::Queen));
+        assert_eq!(m.to_uci(), "e7e8q");
+        assert_eq!(Move::from_uci("g1f3").unwrap().to_uci(), "g1f3");
+        for bad in ["", "e2", "e2e9", "e7e8k", "e2e4x5", "i1a1"] {
+            assert_eq!(Move::from_uci(bad), None, "{}", bad);
+        }
+
+        let b = ChessBoard::new_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(b.parse_uci_move("e1g1").unwrap().to_uci(), "e1g1");
+        assert_eq!(b.parse_uci_move("e1h1").unwrap().to_uci(), "e1g1");
+        assert_eq!(b.parse_uci_move("e1a1").unwrap().to_uci(), "e1c1");
+        assert_eq!(b.parse_uci_move("e1e3"), None);
+        let mut b = ChessBoard::new_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        b.chess960 = true;
+        assert_eq!(b.parse_uci_move("e1h1").unwrap().to_uci(), "e1h1");
+        assert_eq!(b.parse_uci_move("e1g1"), None);
+    }
+
+    #[test]
+    fn perft_standard() {
+        assert_eq!(ChessBoard::new().perft(3), 8902);
+        let kiwipete = ChessBoard::new_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(kiwipete.perft(1), 48);
+        assert_eq!(kiwipete.perft(2), 2039);
+        assert_eq!(kiwipete.perft(3), 97862);
+        let endgame = ChessBoard::new_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(endgame.perft(4), 43238);
+        let promotions = ChessBoard::new_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(promotions.perft(3), 9467);
+        let castling = ChessBoard::new_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(castling.perft(3), 62379);
+    }
+
+    #[test]
+    fn chess960_start_positions() {
+        let standard = ChessBoard::new_chess960(518);
+        assert_eq!(standard.to_fen(), ChessBoard::new().to_fen());
+        assert!(standard.chess960);
+
+        let b = ChessBoard::new_chess960(0);
+        assert_eq!(b.to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(b.to_shredder_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert_eq!(ChessBoard::new_chess960(959).to_shredder_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1");
+
+        let b = ChessBoard::new_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1").unwrap();
+        assert!(b.chess960);
+        assert_eq!(b.castling_rights.value, ChessBoard::new_chess960(0).castling_rights.value);
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // The king already stands on its destination square, so only king-captures-rook can describe the move
+        let mut b = ChessBoard::new_fen("1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1").unwrap();
+        let castle = Move::new(square_from_string("G1".to_string()), square_from_string("H1".to_string()));
+        assert!(b.generate_moves().contains(&castle));
+        b.make_move(castle, true);
+        assert_eq!(b.to_shredder_fen(), "1r4kr/8/8/8/8/8/8/1R3RK1 b hb - 1 1");
+
+        // Castling queenside would pass f8, which the white rook now attacks
+        assert!(!b.generate_moves().contains(&Move::new(square_from_string("G8".to_string()), square_from_string("B8".to_string()))));
+        let castle = Move::new(square_from_string("G8".to_string()), square_from_string("H8".to_string()));
+        assert!(b.generate_moves().contains(&castle));
+        b.make_move(castle, true);
+        assert_eq!(b.to_fen(), "1r3rk1/8/8/8/8/8/8/1R3RK1 w - - 2 2");
+
+        // A rook on the b-file shields the king from the a-file rook until it castles away
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/rRK5 w B - 0 1").unwrap();
+        assert!(!b.generate_moves().contains(&Move::new(square_from_string("C1".to_string()), square_from_string("B1".to_string()))));
+    }
+
+    #[test]
+    fn perft_chess960() {
+        let b = ChessBoard::new_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(b.perft(3), 12189);
+        let b = ChessBoard::new_fen("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9").unwrap();
+        assert_eq!(b.perft(3), 10471);
+        let b = ChessBoard::new_fen("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9").unwrap();
+        assert_eq!(b.perft(3), 13440);
+        let b = ChessBoard::new_fen("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9").unwrap();
+        assert_eq!(b.perft(3), 31058);
+    }
+
+    #[test]
+    fn insufficient_material_on_time() {
+        let mut b = kings_only();
+        b.white_knight.set_square(square_from_string("C3".to_string()));
+        assert!(b.has_insufficient_material(Color::White));
+        assert!(b.has_insufficient_material(Color::Black));
+
+        b.black_pawn.set_square(square_from_string("A7".to_string()));
+        assert!(!b.has_insufficient_material(Color::White));
+        assert!(!b.has_insufficient_material(Color::Black));
+
+        let mut b = kings_only();
+        b.white_bishop.set_square(square_from_string("C1".to_string()));
+        b.black_bishop.set_square(square_from_string("C8".to_string()));
+        assert!(!b.has_insufficient_material(Color::White));
+        assert!(!b.is_insufficient_material());
+
+        let mut b = kings_only();
+        b.white_rook.set_square(square_from_string("A1".to_string()));
+        assert!(!b.has_insufficient_material(Color::White));
+        assert!(b.has_insufficient_material(Color::Black));
+    }
+
+    #[test]
+    fn legal_destinations_and_flags() {
+        let b = ChessBoard::new_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        for from in (0..64).map(square_from_i32) {
+            let mut expected = BitBoard::new(0);
+            b.generate_moves().iter().filter(|m| m.from == from).for_each(|m| expected.set_square(m.to));
+            assert_eq!(b.legal_destinations(from), expected);
+        }
+        let square = |s: &str| square_from_string(s.to_string());
+        assert_eq!(b.legal_destinations(square("E5")).to_squares(), vec![square("D6"), square("E6")]);
+        assert_eq!(b.legal_destinations(square("D5")).value, 0);
+
+        let flags = b.move_flags(&Move::from_uci("e5d6").unwrap());
+        assert_eq!(flags, MoveFlags { capture: true, en_passant: true, ..MoveFlags::default() });
+        let castle = Move::from_uci("e1g1").unwrap();
+        assert_eq!(b.move_flags(&castle), MoveFlags { castle: true, ..MoveFlags::default() });
+        assert_eq!(b.castling_destinations(&castle), Some((square("G1"), square("F1"))));
+        assert_eq!(b.castling_destinations(&Move::from_uci("e1f1").unwrap()), None);
+        let flags = b.move_flags(&Move::from_uci("b7a8q").unwrap());
+        assert_eq!(flags, MoveFlags { capture: true, promotion: true, check: true, ..MoveFlags::default() });
+        let moves = b.get_legal_moves_with_flags(square("B7"));
+        assert_eq!(moves.len(), 8);
+        assert!(moves.iter().all(|(_, flags)| flags.promotion));
+
+        let flags = ChessBoard::new().move_flags(&Move::from_uci("e2e4").unwrap());
+        assert_eq!(flags, MoveFlags { double_push: true, ..MoveFlags::default() });
+    }
 }
@@ -0,0 +1,263 @@
+use crate::{ChessBoard, Move, Piece};
+use crate::eval::piece_value;
+use crate::search::MAX_PLY;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stage {
+    HashMove,
+    GenerateCaptures,
+    WinningCaptures,
+    Promotions,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    LosingCaptures,
+    Done,
+}
+
+// Most valuable victim first, among those the least valuable attacker
+pub fn mvv_lva(board: &ChessBoard, m: &Move) -> i32 {
+    piece_value(board.captured_piece(m)) * 100 - piece_value(board.get_square_piece(m.from))
+}
+
+// Quiet moves that caused a beta cutoff, two per ply
+pub struct KillerMoves {
+    moves: Vec<[Option<Move>; 2]>,
+}
+
+impl Default for KillerMoves {
+    fn default() -> KillerMoves {
+        KillerMoves::new()
+    }
+}
+
+impl KillerMoves {
+    pub fn new() -> KillerMoves {
+        KillerMoves { moves: vec![[None, None]; MAX_PLY as usize + 1] }
+    }
+
+    pub fn add(&mut self, ply: i32, m: Move) {
+        let slot: &mut [Option<Move>; 2] = &mut self.moves[ply as usize];
+        if slot[0] != Some(m) {
+            slot[1] = slot[0];
+            slot[0] = Some(m);
+        }
+    }
+
+    pub fn get(&self, ply: i32) -> [Option<Move>; 2] {
+        self.moves[ply as usize]
+    }
+
+    pub fn clear(&mut self) {
+        self.moves.iter_mut().for_each(|slot| *slot = [None, None]);
+    }
+}
+
+const HISTORY_MAX: i32 = 1 << 16;
+
+// How often a quiet move from one square to another caused a cutoff, weighted by depth, per side
+pub struct History {
+    table: Vec<i32>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+impl History {
+    pub fn new() -> History {
+        History { table: vec![0; 2 * 64 * 64] }
+    }
+
+    fn index(white: bool, m: &Move) -> usize {
+        ((white as usize * 64) + m.from.to_i32() as usize) * 64 + m.to.to_i32() as usize
+    }
+
+    pub fn add(&mut self, white: bool, m: &Move, depth: i32) {
+        let index: usize = History::index(white, m);
+        self.table[index] += depth * depth;
+        // Halving everything keeps the scores bounded and lets newer results count more
+        if self.table[index] > HISTORY_MAX {
+            self.table.iter_mut().for_each(|value| *value /= 2);
+        }
+    }
+
+    pub fn get(&self, white: bool, m: &Move) -> i32 {
+        self.table[History::index(white, m)]
+    }
+
+    pub fn clear(&mut self) {
+        self.table.iter_mut().for_each(|value| *value = 0);
+    }
+}
+
+// Hands out the legal moves of a position best first, only generating each group when it is reached,
+// so a cutoff on the hash move or a capture never pays for the quiet moves
+pub struct MovePicker<'a> {
+    board: &'a ChessBoard,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    killer_index: usize,
+    stage: Stage,
+    next_stage: Stage,
+    moves: Vec<(Move, i32)>,
+    promotions: Vec<(Move, i32)>,
+    losing: Vec<(Move, i32)>,
+}
+
+impl<'a> MovePicker<'a> {
+    pub fn new(board: &'a ChessBoard, hash_move: Option<Move>, killers: [Option<Move>; 2]) -> MovePicker<'a> {
+        MovePicker {
+            board,
+            hash_move,
+            killers,
+            killer_index: 0,
+            stage: Stage::HashMove,
+            next_stage: Stage::HashMove,
+            moves: Vec::new(),
+            promotions: Vec::new(),
+            losing: Vec::new(),
+        }
+    }
+
+    // The stage the last returned move came from
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    fn is_quiet(&self, m: &Move) -> bool {
+        !self.board.is_capture(m) && m.promotion == Piece::None
+    }
+
+    pub fn next_move(&mut self, history: &History) -> Option<Move> {
+        loop {
+            self.stage = self.next_stage;
+            match self.next_stage {
+                Stage::HashMove => {
+                    self.next_stage = Stage::GenerateCaptures;
+                    match self.hash_move {
+                        Some(m) if self.board.is_move_legal(&m) => return Some(m),
+                        _ => self.hash_move = None,
+                    }
+                },
+                Stage::GenerateCaptures => {
+                    for m in self.board.generate_captures() {
+                        if Some(m) == self.hash_move { continue; }
+                        if m.promotion != Piece::None {
+                            self.promotions.push((m, piece_value(m.promotion) * 100 + piece_value(self.board.captured_piece(&m))));
+                        } else if self.board.see_ge(&m, 0) {
+                            self.moves.push((m, mvv_lva(self.board, &m)));
+                        } else {
+                            self.losing.push((m, mvv_lva(self.board, &m)));
+                        }
+                    }
+                    self.next_stage = Stage::WinningCaptures;
+                },
+                Stage::WinningCaptures => match pop_best(&mut self.moves) {
+                    Some(m) => return Some(m),
+                    None => self.next_stage = Stage::Promotions,
+                },
+                Stage::Promotions => match pop_best(&mut self.promotions) {
+                    Some(m) => return Some(m),
+                    None => self.next_stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    while self.killer_index < 2 {
+                        let killer: Option<Move> = self.killers[self.killer_index];
+                        self.killer_index += 1;
+                        match killer {
+                            Some(m) if Some(m) != self.hash_move && self.is_quiet(&m) && self.board.is_move_legal(&m) => return Some(m),
+                            _ => (),
+                        }
+                    }
+                    self.next_stage = Stage::GenerateQuiets;
+                },
+                Stage::GenerateQuiets => {
+                    let white: bool = self.board.white_turn;
+                    for m in self.board.generate_moves() {
+                        if Some(m) == self.hash_move || self.killers.contains(&Some(m)) || !self.is_quiet(&m) { continue; }
+                        self.moves.push((m, history.get(white, &m)));
+                    }
+                    self.next_stage = Stage::Quiets;
+                },
+                Stage::Quiets => match pop_best(&mut self.moves) {
+                    Some(m) => return Some(m),
+                    None => self.next_stage = Stage::LosingCaptures,
+                },
+                Stage::LosingCaptures => match pop_best(&mut self.losing) {
+                    Some(m) => return Some(m),
+                    None => self.next_stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+fn pop_best(moves: &mut Vec<(Move, i32)>) -> Option<Move> {
+    let index: usize = (0..moves.len()).max_by_key(|i| moves[*i].1)?;
+    Some(moves.swap_remove(index).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_from_string;
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(square_from_string(from.to_string()), square_from_string(to.to_string()))
+    }
+
+    fn picked(board: &ChessBoard, hash_move: Option<Move>, killers: [Option<Move>; 2], history: &History) -> Vec<(Move, Stage)> {
+        let mut picker = MovePicker::new(board, hash_move, killers);
+        let mut moves: Vec<(Move, Stage)> = Vec::new();
+        while let Some(m) = picker.next_move(history) {
+            moves.push((m, picker.stage()));
+        }
+        moves
+    }
+
+    #[test]
+    fn yields_every_legal_move_once() {
+        let b = ChessBoard::new_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        // An illegal hash move and killers from elsewhere in the tree are skipped
+        for (hash_move, killers) in [
+            (Some(mv("E1", "G1")), [Some(mv("A2", "A4")), Some(mv("A1", "B1"))]),
+            (Some(mv("E2", "E4")), [Some(mv("F3", "F7")), Some(mv("H1", "H8"))]),
+            (None, [None, None])] {
+            let mut moves: Vec<Move> = picked(&b, hash_move, killers, &History::new()).into_iter().map(|(m, _)| m).collect();
+            let mut expected: Vec<Move> = b.generate_moves();
+            assert_eq!(moves.len(), expected.len());
+            moves.sort_by_key(|m| (m.from.to_i32(), m.to.to_i32()));
+            expected.sort_by_key(|m| (m.from.to_i32(), m.to.to_i32()));
+            assert_eq!(moves, expected);
+        }
+    }
+
+    #[test]
+    fn stage_order() {
+        // Nxf6 wins a bishop, Qxd5 loses the queen to the e6 pawn
+        let b = ChessBoard::new_fen("3rk3/1P6/4pb2/3p4/4N3/8/8/3QK2R w K - 0 1").unwrap();
+        let mut history = History::new();
+        history.add(true, &mv("H1", "H7"), 5);
+        let moves = picked(&b, Some(mv("E1", "G1")), [Some(mv("E1", "F2")), Some(mv("E4", "G5"))], &history);
+        assert_eq!(moves.len(), b.generate_moves().len());
+
+        let b7 = square_from_string("B7".to_string());
+        let b8 = square_from_string("B8".to_string());
+        assert_eq!(moves[..8], [
+            (mv("E1", "G1"), Stage::HashMove),
+            (mv("E4", "F6"), Stage::WinningCaptures),
+            (Move::new_promotion(b7, b8, Piece::Queen), Stage::Promotions),
+            (Move::new_promotion(b7, b8, Piece::Rook), Stage::Promotions),
+            (Move::new_promotion(b7, b8, Piece::Bishop), Stage::Promotions),
+            (Move::new_promotion(b7, b8, Piece::Knight), Stage::Promotions),
+            (mv("E1", "F2"), Stage::Killers),
+            (mv("E4", "G5"), Stage::Killers)]);
+        assert_eq!(moves[8], (mv("H1", "H7"), Stage::Quiets));
+        assert_eq!(moves[moves.len() - 1], (mv("D1", "D5"), Stage::LosingCaptures));
+        assert!(moves[9..moves.len() - 1].iter().all(|(_, stage)| *stage == Stage::Quiets));
+    }
+}
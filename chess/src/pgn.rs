@@ -0,0 +1,319 @@
+use crate::{ChessBoard, Move};
+
+const LINE_WIDTH: usize = 80;
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+    InvalidTag(String),
+    InvalidFen(String),
+    // The ply (0 for the first move) and the move that could not be played
+    IllegalMove(usize, String),
+    UnterminatedComment,
+    UnterminatedVariation,
+}
+
+// One game with its tags and main line, the moves are kept in SAN as written
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl Default for PgnGame {
+    fn default() -> PgnGame {
+        PgnGame::new()
+    }
+}
+
+impl PgnGame {
+    // Starts with the seven tag roster every PGN game should have
+    pub fn new() -> PgnGame {
+        let tags: Vec<(String, String)> = [
+            ("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"),
+            ("White", "?"), ("Black", "?"), ("Result", "*")]
+            .iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        PgnGame { tags, moves: Vec::new(), result: "*".to_string() }
+    }
+
+    pub fn from_moves(start: &ChessBoard, moves: &[Move]) -> PgnGame {
+        let mut game: PgnGame = PgnGame::new();
+        if start.to_fen() != ChessBoard::new().to_fen() || start.chess960 {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &start.to_fen());
+            if start.chess960 { game.set_tag("Variant", "Chess960"); }
+        }
+        let mut board: ChessBoard = *start;
+        for m in moves {
+            game.moves.push(board.to_san(m));
+            board.make_move(*m, false);
+        }
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn start_position(&self) -> Result<ChessBoard, PgnError> {
+        let mut board: ChessBoard = match self.tag("FEN") {
+            Some(fen) => ChessBoard::new_fen(fen).map_err(|_| PgnError::InvalidFen(fen.to_string()))?,
+            None => ChessBoard::new(),
+        };
+        if self.tag("Variant").is_some_and(|v| v.to_lowercase().contains("960") || v.to_lowercase() == "fischerandom") {
+            board.chess960 = true;
+        }
+        Ok(board)
+    }
+
+    // Plays the SAN moves from the start position
+    pub fn board_moves(&self) -> Result<Vec<Move>, PgnError> {
+        let mut board: ChessBoard = self.start_position()?;
+        let mut moves: Vec<Move> = Vec::new();
+        for (ply, san) in self.moves.iter().enumerate() {
+            let m: Move = board.parse_san(san).ok_or(PgnError::IllegalMove(ply, san.clone()))?;
+            board.make_move(m, false);
+            moves.push(m);
+        }
+        Ok(moves)
+    }
+
+    // The position after the last move
+    pub fn board(&self) -> Result<ChessBoard, PgnError> {
+        let mut board: ChessBoard = self.start_position()?;
+        for m in self.board_moves()? {
+            board.make_move(m, false);
+        }
+        Ok(board)
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn: String = write_tags(&self.tags, &self.result);
+
+        let start: Option<ChessBoard> = self.start_position().ok();
+        let mut number: u32 = start.map(|b| b.fullmove_number).unwrap_or(1);
+        let mut white: bool = start.map(|b| b.white_turn).unwrap_or(true);
+        let mut tokens: Vec<String> = Vec::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            if white {
+                tokens.push(format!("{}. {}", number, san));
+            } else if i == 0 {
+                tokens.push(format!("{}... {}", number, san));
+            } else {
+                tokens.push(san.clone());
+            }
+            if !white { number += 1; }
+            white = !white;
+        }
+        tokens.push(self.result.clone());
+        pgn.push_str(&wrap(&tokens));
+        pgn.push('\n');
+        pgn
+    }
+}
+
+// The tag section followed by the empty line before the movetext
+pub(crate) fn write_tags(tags: &[(String, String)], result: &str) -> String {
+    let mut text: String = String::new();
+    for (name, value) in tags.iter() {
+        let value: &str = if name == "Result" { result } else { value };
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    text.push('\n');
+    text
+}
+
+// Joins the tokens with spaces into lines no longer than LINE_WIDTH
+pub(crate) fn wrap(tokens: &[String]) -> String {
+    let mut text: String = String::new();
+    let mut line_length: usize = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > LINE_WIDTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    text
+}
+
+// Reads every game in the text. Comments, variations and NAGs are skipped, only the main line is kept
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game: PgnGame = PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_string() };
+    let mut in_game: bool = false;
+    let mut chars = text.chars().peekable();
+    let mut depth: u32 = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if depth == 0 => {
+                // Tags after movetext start the next game
+                if in_game && !game.moves.is_empty() {
+                    games.push(game);
+                    game = PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_string() };
+                }
+                in_game = true;
+                let mut tag: String = String::new();
+                let mut in_string: bool = false;
+                loop {
+                    match chars.next() {
+                        Some('\\') if in_string => if let Some(escaped) = chars.next() { tag.push(escaped) },
+                        Some('"') => { in_string = !in_string; tag.push('"'); },
+                        Some(']') if !in_string => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(PgnError::InvalidTag(tag)),
+                    }
+                }
+                let (name, value) = parse_tag(&tag).ok_or(PgnError::InvalidTag(tag.clone()))?;
+                if name == "Result" { game.result = value.clone(); }
+                game.set_tag(&name, &value);
+            },
+            '{' => {
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(_) => (),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+            },
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' { break; }
+                }
+            },
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token: String = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "{}();[".contains(*next) { break; }
+                    token.push(chars.next().unwrap());
+                }
+                if depth > 0 || token.starts_with('$') { continue; }
+                in_game = true;
+                if RESULTS.contains(&token.as_str()) {
+                    game.result = token;
+                    games.push(game);
+                    game = PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_string() };
+                    in_game = false;
+                    continue;
+                }
+                let san: &str = strip_move_number(&token);
+                if !san.is_empty() { game.moves.push(san.to_string()); }
+            },
+        }
+    }
+    if depth > 0 { return Err(PgnError::UnterminatedVariation); }
+    if in_game { games.push(game); }
+    Ok(games)
+}
+
+// Move numbers like 12. or 12... may be glued to the move, castling written with zeros is left alone
+pub(crate) fn strip_move_number(token: &str) -> &str {
+    let rest: &str = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() < token.len() && rest.starts_with('.') { rest.trim_start_matches('.') } else { token }
+}
+
+pub(crate) fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let tag: &str = tag.trim();
+    let space: usize = tag.find(char::is_whitespace)?;
+    let name: &str = &tag[..space];
+    let value: &str = tag[space..].trim();
+    if name.is_empty() || !value.starts_with('"') || !value.ends_with('"') || value.len() < 2 { return None; }
+    Some((name.to_string(), value[1..value.len() - 1].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 d6 3.d4 Bg4 {This is a weak move already.} 4.dxe5 Bxf3 5.Qxf3 dxe5
+6.Bc4 Nf6 7.Qb3 Qe7 8.Nc3 c6 9.Bg5 b5 (9...Qb4 10.Qxb4) 10.Nxb5 cxb5 11.Bxb5+ Nbd7
+12.O-O-O Rd8 13.Rxd7 Rxd7 14.Rd1 Qe6 15.Bxd7+ Nxd7 16.Qb8+ $1 Nxb8 17.Rd8# 1-0
+"#;
+
+    #[test]
+    fn parse_and_replay() {
+        let games = parse_pgn(&format!("{}\n{}", OPERA_GAME, "[White \"A\"]\n[Black \"B\"]\n\n1. d4 d5 *\n")).unwrap();
+        assert_eq!(games.len(), 2);
+        let game = &games[0];
+        assert_eq!(game.tag("White"), Some("Paul Morphy"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.moves.len(), 33);
+        assert_eq!(game.moves[32], "Rd8#");
+        let board = game.board().unwrap();
+        assert!(board.is_black_mated());
+        assert_eq!(board.to_fen(), "1n1Rkb1r/p4ppp/4q3/4p1B1/4P3/8/PPP2PPP/2K5 b k - 1 17");
+
+        assert_eq!(games[1].moves, vec!["d4", "d5"]);
+        assert_eq!(games[1].result, "*");
+    }
+
+    #[test]
+    fn castling_with_zeros() {
+        let text = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 d6 5. d3 Bg4 6. Nc3 Qd7 7.Be3 0-0-0 *";
+        let game = &parse_pgn(text).unwrap()[0];
+        assert_eq!(game.moves[6], "0-0");
+        assert_eq!(game.moves[13], "0-0-0");
+        assert_eq!(game.board().unwrap().to_fen(), "2kr2nr/pppq1ppp/2np4/2b1p3/2B1P1b1/2NPBN2/PPP2PPP/R2Q1RK1 w - - 5 8");
+        assert_eq!(strip_move_number("12...Nf6"), "Nf6");
+        assert_eq!(strip_move_number("12."), "");
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let game = &parse_pgn(OPERA_GAME).unwrap()[0];
+        let moves = game.board_moves().unwrap();
+        let mut exported = PgnGame::from_moves(&ChessBoard::new(), &moves);
+        for (name, value) in game.tags.iter() {
+            exported.set_tag(name, value);
+        }
+        exported.result = game.result.clone();
+        let text = exported.to_pgn();
+        assert!(text.starts_with("[Event \"Paris\"]\n"));
+        assert!(text.contains("\n\n1. e4 e5 2. Nf3 d6"));
+        assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(text.trim_end().ends_with("17. Rd8# 1-0"));
+        assert_eq!(parse_pgn(&text).unwrap()[0].moves, game.moves);
+
+        // A game from a position with black to move
+        let start = ChessBoard::new_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40").unwrap();
+        let m = start.parse_san("Kd7").unwrap();
+        let game = PgnGame::from_moves(&start, &[m]);
+        assert_eq!(game.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"));
+        assert!(game.to_pgn().ends_with("\n40... Kd7 *\n"));
+        assert_eq!(parse_pgn(&game.to_pgn()).unwrap()[0].board().unwrap().to_fen(), "8/3k4/8/8/8/8/4P3/4K3 w - - 1 41");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_pgn("[Event Paris]\n1. e4 *"), Err(PgnError::InvalidTag("Event Paris".to_string())));
+        assert_eq!(parse_pgn("1. e4 {open"), Err(PgnError::UnterminatedComment));
+        assert_eq!(parse_pgn("1. e4 (1. d4"), Err(PgnError::UnterminatedVariation));
+        let game = &parse_pgn("1. e4 e5 2. Ke3 *").unwrap()[0];
+        assert_eq!(game.board_moves(), Err(PgnError::IllegalMove(2, "Ke3".to_string())));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use crate::{piece_to_char, BitBoard, ChessBoard, Color, Move, Piece, Square, LIGHT_SQUARES};
+
+const RESET: &str = "\x1b[0m";
+const LIGHT_BACKGROUND: &str = "\x1b[48;5;180m";
+const DARK_BACKGROUND: &str = "\x1b[48;5;137m";
+const LAST_MOVE_BACKGROUND: &str = "\x1b[48;5;143m";
+const CHECK_BACKGROUND: &str = "\x1b[48;5;167m";
+const WHITE_FOREGROUND: &str = "\x1b[1;97m";
+const BLACK_FOREGROUND: &str = "\x1b[1;30m";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RenderOptions {
+    pub unicode: bool,
+    // Black at the bottom
+    pub flipped: bool,
+    pub coordinates: bool,
+    pub last_move: Option<Move>,
+    // Marks the king of the side to move when it is in check
+    pub highlight_check: bool,
+    // ANSI escape codes for square and piece colors, otherwise highlights are drawn with brackets
+    pub ansi_colors: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            unicode: false,
+            flipped: false,
+            coordinates: true,
+            last_move: None,
+            highlight_check: true,
+            ansi_colors: false,
+        }
+    }
+}
+
+pub fn piece_symbol(piece: Piece, color: Color, unicode: bool) -> char {
+    if !unicode { return piece_to_char(piece, color); }
+    match (piece, color) {
+        (Piece::King, Color::White) => '♔',
+        (Piece::Queen, Color::White) => '♕',
+        (Piece::Rook, Color::White) => '♖',
+        (Piece::Bishop, Color::White) => '♗',
+        (Piece::Knight, Color::White) => '♘',
+        (Piece::Pawn, Color::White) => '♙',
+        (Piece::King, _) => '♚',
+        (Piece::Queen, _) => '♛',
+        (Piece::Rook, _) => '♜',
+        (Piece::Bishop, _) => '♝',
+        (Piece::Knight, _) => '♞',
+        (Piece::Pawn, _) => '♟',
+        (Piece::None, _) => ' ',
+    }
+}
+
+// One line per rank, every square three characters wide
+pub fn render(board: &ChessBoard, options: &RenderOptions) -> String {
+    let rows: Vec<i32> = if options.flipped { (0..8).collect() } else { (0..8).rev().collect() };
+    let columns: Vec<i32> = if options.flipped { (0..8).rev().collect() } else { (0..8).collect() };
+
+    let mut last_move: BitBoard = BitBoard::new(0);
+    if let Some(m) = options.last_move {
+        last_move.set_square(m.from);
+        last_move.set_square(m.to);
+    }
+    let mut check: BitBoard = BitBoard::new(0);
+    if options.highlight_check {
+        if board.white_turn && board.is_white_checked() { check = board.white_king; }
+        if !board.white_turn && board.is_black_checked() { check = board.black_king; }
+    }
+
+    let mut text: String = String::new();
+    for row in rows.iter() {
+        if options.coordinates { text.push_str(&format!("{} ", row + 1)); }
+        for column in columns.iter() {
+            let square: Square = Square::new(*row, *column);
+            let piece: Piece = board.get_square_piece(square);
+            let color: Color = board.get_square_color(square);
+            let symbol: char = match piece {
+                Piece::None if options.ansi_colors => ' ',
+                Piece::None if options.unicode => '·',
+                Piece::None => '.',
+                _ => piece_symbol(piece, color, options.unicode),
+            };
+
+            if options.ansi_colors {
+                let background: &str = if check.get_square(square) {
+                    CHECK_BACKGROUND
+                } else if last_move.get_square(square) {
+                    LAST_MOVE_BACKGROUND
+                } else if LIGHT_SQUARES & (1 << square.to_i32()) != 0 {
+                    LIGHT_BACKGROUND
+                } else {
+                    DARK_BACKGROUND
+                };
+                let foreground: &str = if color == Color::White { WHITE_FOREGROUND } else { BLACK_FOREGROUND };
+                text.push_str(&format!("{}{} {} ", background, foreground, symbol));
+            } else if check.get_square(square) {
+                text.push_str(&format!("({})", symbol));
+            } else if last_move.get_square(square) {
+                text.push_str(&format!("[{}]", symbol));
+            } else {
+                text.push_str(&format!(" {} ", symbol));
+            }
+        }
+        if options.ansi_colors { text.push_str(RESET); }
+        // No trailing spaces, they only get in the way when comparing boards
+        let trimmed: usize = text.trim_end_matches(' ').len();
+        text.truncate(trimmed);
+        text.push('\n');
+    }
+    if options.coordinates {
+        text.push_str("  ");
+        for column in columns.iter() {
+            text.push_str(&format!(" {} ", (b'a' + *column as u8) as char));
+        }
+        let trimmed: usize = text.trim_end_matches(' ').len();
+        text.truncate(trimmed);
+        text.push('\n');
+    }
+    text
+}
+
+impl fmt::Display for ChessBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", render(self, &RenderOptions::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_from_string;
+
+    #[test]
+    fn display() {
+        let b = ChessBoard::new();
+        assert_eq!(b.to_string(), "\
+8  r  n  b  q  k  b  n  r
+7  p  p  p  p  p  p  p  p
+6  .  .  .  .  .  .  .  .
+5  .  .  .  .  .  .  .  .
+4  .  .  .  .  .  .  .  .
+3  .  .  .  .  .  .  .  .
+2  P  P  P  P  P  P  P  P
+1  R  N  B  Q  K  B  N  R
+   a  b  c  d  e  f  g  h
+");
+    }
+
+    #[test]
+    fn options() {
+        // After 1.f3 e5 2.g4 Qh4#
+        let b = ChessBoard::new_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let last_move = Move::new(square_from_string("D8".to_string()), square_from_string("H4".to_string()));
+        let options = RenderOptions { unicode: true, flipped: true, last_move: Some(last_move), ..RenderOptions::default() };
+        assert_eq!(render(&b, &options), "\
+1  ♖  ♘  ♗ (♔) ♕  ♗  ♘  ♖
+2  ♙  ·  ·  ♙  ♙  ♙  ♙  ♙
+3  ·  ·  ♙  ·  ·  ·  ·  ·
+4 [♛] ♙  ·  ·  ·  ·  ·  ·
+5  ·  ·  ·  ♟  ·  ·  ·  ·
+6  ·  ·  ·  ·  ·  ·  ·  ·
+7  ♟  ♟  ♟  ·  ♟  ♟  ♟  ♟
+8  ♜  ♞  ♝  ♚ [·] ♝  ♞  ♜
+   h  g  f  e  d  c  b  a
+");
+
+        let options = RenderOptions { coordinates: false, highlight_check: false, ..RenderOptions::default() };
+        let text = render(&b, &options);
+        assert_eq!(text.lines().count(), 8);
+        assert_eq!(text.lines().next(), Some(" r  n  b  .  k  b  n  r"));
+        assert!(!text.contains('('));
+
+        let options = RenderOptions { ansi_colors: true, last_move: Some(last_move), ..RenderOptions::default() };
+        let text = render(&b, &options);
+        assert!(text.contains(&format!("{}{} K ", CHECK_BACKGROUND, WHITE_FOREGROUND)));
+        assert!(text.contains(&format!("{}{} q ", LAST_MOVE_BACKGROUND, BLACK_FOREGROUND)));
+        assert_eq!(text.matches(RESET).count(), 8);
+    }
+}
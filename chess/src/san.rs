@@ -0,0 +1,188 @@
+use crate::{piece_to_char, ChessBoard, Color, Move, Piece, Square};
+
+fn square_name(square: Square) -> String {
+    square.to_string().to_lowercase()
+}
+
+impl ChessBoard {
+    // Standard algebraic notation, like Nf3, exd5, O-O or e8=Q+
+    pub fn to_san(&self, m: &Move) -> String {
+        let mut san: String = self.san_without_suffix(m, &self.generate_moves());
+        let mut after: ChessBoard = *self;
+        after.make_move(*m, false);
+        if after.is_white_mated() || after.is_black_mated() {
+            san.push('#');
+        } else if (after.white_turn && after.is_white_checked()) || (!after.white_turn && after.is_black_checked()) {
+            san.push('+');
+        }
+        san
+    }
+
+    fn san_without_suffix(&self, m: &Move, moves: &[Move]) -> String {
+        if let Some(rook) = self.castling_rook(m) {
+            return if rook.column > m.from.column { "O-O".to_string() } else { "O-O-O".to_string() };
+        }
+        let piece: Piece = self.get_square_piece(m.from);
+        let capture: bool = self.is_capture(m);
+        let mut san: String = String::new();
+
+        if piece == Piece::Pawn {
+            if capture {
+                san.push_str(&square_name(m.from)[0..1]);
+                san.push('x');
+            }
+            san.push_str(&square_name(m.to));
+            if m.promotion != Piece::None {
+                san.push('=');
+                san.push(piece_to_char(m.promotion, Color::White));
+            }
+            return san;
+        }
+
+        san.push(piece_to_char(piece, Color::White));
+        // Only as much of the from square as is needed to tell it apart from the same piece type
+        let others: Vec<Square> = moves.iter()
+            .filter(|x| x.to == m.to && x.from != m.from && self.get_square_piece(x.from) == piece && self.castling_rook(x).is_none())
+            .map(|x| x.from)
+            .collect();
+        if !others.is_empty() {
+            let from: String = square_name(m.from);
+            if others.iter().all(|s| s.column != m.from.column) {
+                san.push_str(&from[0..1]);
+            } else if others.iter().all(|s| s.row != m.from.row) {
+                san.push_str(&from[1..2]);
+            } else {
+                san.push_str(&from);
+            }
+        }
+        if capture { san.push('x'); }
+        san.push_str(&square_name(m.to));
+        san
+    }
+
+    // Accepts check and annotation marks, 0-0 for castling and promotions without the =
+    pub fn parse_san(&self, value: &str) -> Option<Move> {
+        let normalize = |s: &str| -> String {
+            s.trim_end_matches(['+', '#', '!', '?'])
+                .replace('0', "O")
+                .replace('=', "")
+        };
+        let value: String = normalize(value.trim());
+        if value.is_empty() { return None; }
+        let moves: Vec<Move> = self.generate_moves();
+        moves.iter().copied().find(|m| normalize(&self.san_without_suffix(m, &moves)) == value)
+            .or_else(|| self.parse_loose_san(&value, &moves))
+    }
+
+    // Over-disambiguated moves like Ngf3 or Ng1f3, accepted when exactly one legal move fits
+    fn parse_loose_san(&self, value: &str, moves: &[Move]) -> Option<Move> {
+        let mut chars: Vec<char> = value.chars().filter(|c| *c != 'x').collect();
+        let promotion: Piece = match chars.last() {
+            Some('N') => Piece::Knight,
+            Some('B') => Piece::Bishop,
+            Some('R') => Piece::Rook,
+            Some('Q') => Piece::Queen,
+            _ => Piece::None,
+        };
+        if promotion != Piece::None { chars.pop(); }
+        let piece: Piece = match chars.first() {
+            Some('N') => Piece::Knight,
+            Some('B') => Piece::Bishop,
+            Some('R') => Piece::Rook,
+            Some('Q') => Piece::Queen,
+            Some('K') => Piece::King,
+            _ => Piece::Pawn,
+        };
+        if piece != Piece::Pawn { chars.remove(0); }
+        if chars.len() < 2 { return None; }
+
+        let to: String = chars[chars.len() - 2..].iter().collect();
+        let mut file: Option<i32> = None;
+        let mut rank: Option<i32> = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' if file.is_none() => file = Some(*c as i32 - 'a' as i32),
+                '1'..='8' if rank.is_none() => rank = Some(*c as i32 - '1' as i32),
+                _ => return None,
+            }
+        }
+
+        let mut matching = moves.iter().filter(|m| {
+            square_name(m.to) == to && m.promotion == promotion &&
+            self.get_square_piece(m.from) == piece && self.castling_rook(m).is_none() &&
+            file.is_none_or(|f| m.from.column == f) && rank.is_none_or(|r| m.from.row == r)
+        });
+        let m: Move = *matching.next()?;
+        if matching.next().is_some() { return None; }
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sans(fen: &str) -> Vec<String> {
+        let b = ChessBoard::new_fen(fen).unwrap();
+        b.generate_moves().iter().map(|m| b.to_san(m)).collect()
+    }
+
+    #[test]
+    fn to_san() {
+        let start = sans("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(start.contains(&"e4".to_string()) && start.contains(&"Nf3".to_string()));
+
+        // Knights on b1 and f1 both reach d2, rooks on a1 and a5 both reach a3
+        let moves = sans("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1");
+        for san in ["Nbd2", "Nfd2", "R1a3", "R5a3", "R1a2", "Rh5"] {
+            assert!(moves.contains(&san.to_string()), "{}", san);
+        }
+        // Three queens reach e4, the one on h1 shares a file with one and a rank with the other
+        let moves = sans("4k3/8/8/8/7Q/8/8/3KQ2Q w - - 0 1");
+        for san in ["Qh1e4+", "Q4e4+", "Qee4+"] {
+            assert!(moves.contains(&san.to_string()), "{}", san);
+        }
+
+        let moves = sans("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        for san in ["O-O", "O-O-O", "exd6", "b8=Q+", "bxa8=N", "Rxa8+"] {
+            assert!(moves.contains(&san.to_string()), "{}", san);
+        }
+        let moves = sans("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(moves.contains(&"Ra8#".to_string()));
+    }
+
+    #[test]
+    fn parse_san_round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"] {
+            let b = ChessBoard::new_fen(fen).unwrap();
+            for m in b.generate_moves() {
+                assert_eq!(b.parse_san(&b.to_san(&m)), Some(m), "{} {}", fen, b.to_san(&m));
+            }
+        }
+        let b = ChessBoard::new_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(b.parse_san("0-0").map(|m| b.to_san(&m)), Some("O-O".to_string()));
+        assert_eq!(b.parse_san("b8Q!").unwrap().promotion, Piece::Queen);
+        assert_eq!(b.parse_san("Ke3"), None);
+        assert_eq!(b.parse_san("xyz"), None);
+    }
+
+    #[test]
+    fn parse_san_over_disambiguated() {
+        let b = ChessBoard::new();
+        let nf3 = b.parse_san("Nf3");
+        assert!(nf3.is_some());
+        assert_eq!(b.parse_san("Ngf3"), nf3);
+        assert_eq!(b.parse_san("Ng1f3"), nf3);
+        assert_eq!(b.parse_san("Ng1xf3+"), nf3);
+        assert_eq!(b.parse_san("Nbf3"), None);
+        assert_eq!(b.parse_san("e2e4"), b.parse_san("e4"));
+
+        // Still ambiguous without the file, so Nd2 is rejected
+        let b = ChessBoard::new_fen("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1").unwrap();
+        assert_eq!(b.parse_san("Nd2"), None);
+        assert!(b.parse_san("Nb1d2").is_some());
+    }
+}
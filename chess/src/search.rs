@@ -0,0 +1,496 @@
+use std::time::Duration;
+
+use crate::{ChessBoard, Move, Piece};
+use crate::eval::{evaluate, piece_value};
+use crate::movepick::{mvv_lva, History, KillerMoves, MovePicker};
+use crate::syzygy::{Tablebase, Wdl};
+use crate::timeman::{StopFlag, TimeManager};
+use crate::tt::{Bound, TranspositionTable};
+
+pub const MAX_PLY: i32 = 128;
+pub const MATE_SCORE: i32 = 100000;
+// Tablebase wins stay below every mate score, so a real mate is still preferred
+pub const TB_WIN_SCORE: i32 = MATE_SCORE - 2 * MAX_PLY;
+// Tablebase wins are reported to UCI as this many centipawns minus the plies to the table position
+pub const TB_WIN_CENTIPAWNS: i32 = 20000;
+const INFINITY: i32 = 1000000;
+pub const DEFAULT_HASH_MB: usize = 16;
+// Captures that can not lift the score back to alpha even with this much positional gain are skipped
+const DELTA_MARGIN: i32 = 200;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Score {
+    Centipawns(i32),
+    // Moves until mate, negative when the side to move is getting mated
+    Mate(i32),
+    // Plies until a position the tablebase scores as won, negative when the side to move loses
+    Tablebase(i32),
+}
+
+impl Score {
+    pub fn from_value(value: i32) -> Score {
+        if value >= MATE_SCORE - MAX_PLY {
+            Score::Mate((MATE_SCORE - value + 1) / 2)
+        } else if value <= -MATE_SCORE + MAX_PLY {
+            Score::Mate(-(MATE_SCORE + value) / 2)
+        } else if value >= TB_WIN_SCORE - MAX_PLY {
+            Score::Tablebase(TB_WIN_SCORE - value)
+        } else if value <= -TB_WIN_SCORE + MAX_PLY {
+            Score::Tablebase(-(TB_WIN_SCORE + value))
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+}
+
+// Times are in milliseconds, like the UCI go command
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    // Only ends through the stop flag, other limits are ignored
+    pub infinite: bool,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits { nodes: Some(nodes), ..SearchLimits::default() }
+    }
+
+    pub fn movetime(movetime: u64) -> SearchLimits {
+        SearchLimits { movetime: Some(movetime), ..SearchLimits::default() }
+    }
+
+    pub fn infinite() -> SearchLimits {
+        SearchLimits { infinite: true, ..SearchLimits::default() }
+    }
+
+    pub fn clock(wtime: u64, btime: u64, winc: u64, binc: u64) -> SearchLimits {
+        SearchLimits { wtime: Some(wtime), btime: Some(btime), winc: Some(winc), binc: Some(binc), ..SearchLimits::default() }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: Score,
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    pub tbhits: u64,
+    pub depth: u32,
+}
+
+// Reported after every finished iteration
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: Score,
+    pub nodes: u64,
+    pub tbhits: u64,
+    pub time: Duration,
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
+
+pub fn best_move(board: &ChessBoard, limits: SearchLimits) -> SearchResult {
+    let mut tt: TranspositionTable = TranspositionTable::new(DEFAULT_HASH_MB);
+    best_move_with_table(board, limits, &mut tt)
+}
+
+// Keeps the table around between searches, e.g. for every move of a game
+pub fn best_move_with_table(board: &ChessBoard, limits: SearchLimits, tt: &mut TranspositionTable) -> SearchResult {
+    search(board, limits, tt, &StopFlag::new())
+}
+
+// The search ends early once the flag is set, from any thread
+pub fn search(board: &ChessBoard, limits: SearchLimits, tt: &mut TranspositionTable, stop: &StopFlag) -> SearchResult {
+    search_with_info(board, limits, tt, stop, &mut |_| ())
+}
+
+pub fn search_with_info(board: &ChessBoard, limits: SearchLimits, tt: &mut TranspositionTable, stop: &StopFlag, info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+    search_with_tablebase(board, limits, tt, stop, None, info)
+}
+
+// Root moves that throw away the tablebase result are left out, and the tables end the search once few pieces are left
+pub fn search_with_tablebase(board: &ChessBoard, limits: SearchLimits, tt: &mut TranspositionTable, stop: &StopFlag, tablebase: Option<&Tablebase>, info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+    tt.new_search();
+    let mut searcher: Searcher = Searcher { 
+        limits, 
+        time: TimeManager::new(&limits, board.white_turn),
+        stop,
+        info,
+        tt,
+        tablebase,
+        root_moves: Vec::new(),
+        killers: KillerMoves::new(),
+        history: History::new(),
+        nodes: 0, 
+        tbhits: 0,
+        stopped: false, 
+        pv: vec![Vec::new(); MAX_PLY as usize + 1], 
+        previous_pv: Vec::new(),
+    };
+    searcher.iterative_deepening(board)
+}
+
+struct Searcher<'a> {
+    limits: SearchLimits,
+    time: TimeManager,
+    stop: &'a StopFlag,
+    info: &'a mut dyn FnMut(&SearchInfo),
+    tt: &'a mut TranspositionTable,
+    tablebase: Option<&'a Tablebase>,
+    // Only these moves are searched at the root when not empty
+    root_moves: Vec<Move>,
+    killers: KillerMoves,
+    history: History,
+    nodes: u64,
+    tbhits: u64,
+    stopped: bool,
+    // Triangular PV table, pv[ply] holds the best line found from that ply
+    pv: Vec<Vec<Move>>,
+    previous_pv: Vec<Move>,
+}
+
+impl<'a> Searcher<'a> {
+    fn iterative_deepening(&mut self, board: &ChessBoard) -> SearchResult {
+        let mut result: SearchResult = SearchResult {
+            best_move: board.generate_moves().first().copied(),
+            score: Score::Centipawns(0),
+            pv: Vec::new(),
+            nodes: 0,
+            tbhits: 0,
+            depth: 0,
+        };
+        // Keep the best outcome the tables promise, counting the fifty move rule
+        if let Some(moves) = self.tablebase.and_then(|tb| tb.root_moves(board)) {
+            if let Some(best) = moves.iter().map(|r| r.wdl).max() {
+                self.root_moves = moves.iter().filter(|r| r.wdl == best).map(|r| r.m).collect();
+                self.tbhits += moves.len() as u64;
+                result.best_move = self.root_moves.first().copied();
+            }
+        }
+        let max_depth: u32 = match self.limits.depth {
+            Some(depth) if !self.limits.infinite => depth.min(MAX_PLY as u32),
+            _ => MAX_PLY as u32,
+        };
+
+        for depth in 1..(max_depth + 1) {
+            self.previous_pv = result.pv.clone();
+            let value: i32 = self.negamax(board, depth as i32, 0, -INFINITY, INFINITY, true);
+            let mut pv: Vec<Move> = self.pv[0].clone();
+            self.extend_pv(board, &mut pv, depth as usize);
+
+            // An interrupted iteration is only trusted as far as the previous one got
+            if self.stopped && depth > 1 { break; }
+            result.best_move = pv.first().copied().or(result.best_move);
+            result.score = Score::from_value(value);
+            result.pv = pv;
+            result.depth = depth;
+            (self.info)(&SearchInfo {
+                depth,
+                score: result.score,
+                nodes: self.nodes,
+                tbhits: self.tbhits,
+                time: self.time.elapsed(),
+                hashfull: self.tt.hashfull(),
+                pv: result.pv.clone(),
+            });
+            if self.stopped { break; }
+
+            // A mate found within the searched depth can not get any shorter
+            if let Score::Mate(n) = result.score {
+                if n.unsigned_abs() * 2 <= depth && !self.limits.infinite { break; }
+            }
+            // The next iteration would most likely not finish in time
+            if self.time.soft_limit_reached() { break; }
+        }
+        result.nodes = self.nodes;
+        result.tbhits = self.tbhits;
+        result
+    }
+
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if let Some(nodes) = self.limits.nodes {
+            if self.nodes >= nodes && !self.limits.infinite { self.stopped = true; }
+        }
+        // Looking at the clock and the flag is slow compared to a node, so only every so often
+        if self.nodes.is_multiple_of(1024) && (self.stop.is_stopped() || self.time.hard_limit_reached()) {
+            self.stopped = true;
+        }
+    }
+
+    // Lines cut short by table hits are completed with the best moves stored in the table
+    fn extend_pv(&self, board: &ChessBoard, pv: &mut Vec<Move>, depth: usize) {
+        let mut current: ChessBoard = *board;
+        for m in pv.iter() {
+            current.make_move(*m, false);
+        }
+        while pv.len() < depth {
+            let m: Move = match self.tt.probe(current.zobrist_key(), 0).and_then(|entry| entry.best_move) {
+                Some(m) if current.generate_moves().contains(&m) => m,
+                _ => break,
+            };
+            current.make_move(m, false);
+            pv.push(m);
+        }
+    }
+
+    fn negamax(&mut self, board: &ChessBoard, depth: i32, ply: i32, mut alpha: i32, beta: i32, follow_pv: bool) -> i32 {
+        self.pv[ply as usize].clear();
+        self.count_node();
+        if self.stopped { return 0; }
+
+        if ply > 0 && (board.halfmove_clock >= 100 || board.is_insufficient_material()) { return 0; }
+
+        // Right after a capture or pawn move the tables know the result, later the fifty move rule could change it
+        if ply > 0 && board.halfmove_clock == 0 {
+            if let Some(wdl) = self.tablebase.and_then(|tb| tb.probe_wdl(board)) {
+                self.tbhits += 1;
+                return match wdl {
+                    Wdl::Win => TB_WIN_SCORE - ply,
+                    Wdl::CursedWin => 1,
+                    Wdl::Draw => 0,
+                    Wdl::BlessedLoss => -1,
+                    Wdl::Loss => -TB_WIN_SCORE + ply,
+                };
+            }
+        }
+
+        if ply >= MAX_PLY { return evaluate(board); }
+        if depth <= 0 { return self.quiescence(board, ply, alpha, beta); }
+
+        let key: u64 = board.zobrist_key();
+        let mut hash_move: Option<Move> = None;
+        if let Some(entry) = self.tt.probe(key, ply) {
+            hash_move = entry.best_move;
+            let usable: bool = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if ply > 0 && entry.depth >= depth && usable { return entry.score; }
+        }
+
+        // Search the principal variation of the previous iteration first, otherwise the move from the table
+        let pv_move: Option<Move> = if follow_pv { self.previous_pv.get(ply as usize).copied() } else { None };
+        let mut picker: MovePicker = MovePicker::new(board, pv_move.or(hash_move), self.killers.get(ply));
+
+        let original_alpha: i32 = alpha;
+        let mut best: Option<Move> = None;
+        let mut searched: u32 = 0;
+
+        while let Some(m) = picker.next_move(&self.history) {
+            if ply == 0 && !self.root_moves.is_empty() && !self.root_moves.contains(&m) { continue; }
+            searched += 1;
+            let mut child: ChessBoard = *board;
+            child.make_move(m, false);
+            let value: i32 = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, pv_move == Some(m));
+            if self.stopped { return 0; }
+
+            if value > alpha {
+                alpha = value;
+                best = Some(m);
+                let mut line: Vec<Move> = vec![m];
+                line.extend(self.pv[ply as usize + 1].iter().copied());
+                self.pv[ply as usize] = line;
+                if alpha >= beta {
+                    if !board.is_capture(&m) && m.promotion == Piece::None {
+                        self.killers.add(ply, m);
+                        self.history.add(board.white_turn, &m, depth);
+                    }
+                    break;
+                }
+            }
+        }
+
+        if searched == 0 {
+            let checked: bool = if board.white_turn { board.is_white_checked() } else { board.is_black_checked() };
+            return if checked { -MATE_SCORE + ply } else { 0 };
+        }
+
+        let bound: Bound = if alpha >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(key, depth, bound, alpha, best, ply);
+        alpha
+    }
+
+    // Only captures and promotions are searched until the position is quiet, so exchanges are never cut off halfway
+    fn quiescence(&mut self, board: &ChessBoard, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.pv[ply as usize].clear();
+        self.count_node();
+        if self.stopped { return 0; }
+        if ply >= MAX_PLY { return evaluate(board); }
+
+        // In check there is no standing pat, every evasion is searched
+        let checked: bool = if board.white_turn { board.is_white_checked() } else { board.is_black_checked() };
+        let stand_pat: i32 = if checked { -INFINITY } else { evaluate(board) };
+        let mut moves: Vec<Move> = if checked { board.generate_moves() } else { board.generate_captures() };
+        if checked && moves.is_empty() { return -MATE_SCORE + ply; }
+
+        if stand_pat >= beta { return beta; }
+        if stand_pat > alpha { alpha = stand_pat; }
+
+        // Most valuable victim first, then least valuable attacker
+        moves.sort_by_key(|m| -mvv_lva(board, m));
+
+        for m in moves.iter() {
+            if !checked && m.promotion == Piece::None && stand_pat + piece_value(board.captured_piece(m)) + DELTA_MARGIN <= alpha {
+                continue;
+            }
+            // Captures that lose material are left out, they hardly ever change the result
+            if !checked && !board.see_ge(m, 0) { continue; }
+            let mut child: ChessBoard = *board;
+            child.make_move(*m, false);
+            let value: i32 = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            if self.stopped { return 0; }
+
+            if value > alpha {
+                alpha = value;
+                if alpha >= beta { break; }
+            }
+        }
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_from_string;
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(square_from_string(from.to_string()), square_from_string(to.to_string()))
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let b = ChessBoard::new_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(3));
+        assert_eq!(result.best_move, Some(mv("A1", "A8")));
+        assert_eq!(result.score, Score::Mate(1));
+        assert_eq!(result.pv, vec![mv("A1", "A8")]);
+    }
+
+    #[test]
+    fn mate_in_two() {
+        // Morphy: 1.Ra6 bxa6 2.b7#
+        let b = ChessBoard::new_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(4));
+        assert_eq!(result.best_move, Some(mv("A1", "A6")));
+        assert_eq!(result.score, Score::Mate(2));
+        assert_eq!(result.pv.len(), 3);
+        assert!(result.nodes > 0);
+    }
+
+    #[test]
+    fn mate_in_three() {
+        let mut b = ChessBoard::new_fen("k7/8/8/3K4/8/8/8/7R w - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(6));
+        assert_eq!(result.score, Score::Mate(3));
+        assert_eq!(result.pv.len(), 5);
+        for m in result.pv {
+            b.make_move(m, true);
+        }
+        assert!(b.is_black_mated());
+    }
+
+    #[test]
+    fn getting_mated() {
+        // Kg8 is forced and Qg7 mates
+        let b = ChessBoard::new_fen("7k/8/6KQ/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(3));
+        assert_eq!(result.score, Score::Mate(-1));
+        assert_eq!(result.pv.len(), 2);
+    }
+
+    #[test]
+    fn wins_material() {
+        let b = ChessBoard::new_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(2));
+        assert_eq!(result.best_move, Some(mv("D1", "D5")));
+    }
+
+    #[test]
+    fn reuses_table() {
+        let b = ChessBoard::new_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let first = best_move_with_table(&b, SearchLimits::depth(3), &mut tt);
+        assert!(tt.hashfull() > 0);
+        let second = best_move_with_table(&b, SearchLimits::depth(3), &mut tt);
+        assert!(second.nodes < first.nodes);
+        assert_eq!(second.score, first.score);
+        assert_eq!(second.pv.len(), 3);
+    }
+
+    #[test]
+    fn sees_recaptures() {
+        // Qxe5 wins a pawn at depth one only if dxe5 is never looked at
+        let b = ChessBoard::new_fen("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1").unwrap();
+        let result = best_move(&b, SearchLimits::depth(1));
+        assert_ne!(result.best_move, Some(mv("E1", "E5")));
+        match result.score {
+            Score::Centipawns(value) => assert!(value < 800),
+            score => panic!("unexpected {:?}", score),
+        }
+    }
+
+    #[test]
+    fn movetime_limit() {
+        let start = std::time::Instant::now();
+        let result = best_move(&ChessBoard::new(), SearchLimits::movetime(200));
+        assert!(start.elapsed() < std::time::Duration::from_millis(1000));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn infinite_until_stopped() {
+        let stop = StopFlag::new();
+        let flag = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut tt = TranspositionTable::new(1);
+            search(&ChessBoard::new(), SearchLimits::infinite(), &mut tt, &flag)
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        stop.stop();
+        let result = handle.join().unwrap();
+        assert!(result.best_move.is_some());
+        assert!(result.depth >= 1);
+    }
+
+    #[test]
+    fn reports_iterations() {
+        let mut tt = TranspositionTable::new(1);
+        let mut depths: Vec<u32> = Vec::new();
+        let result = search_with_info(&ChessBoard::new(), SearchLimits::depth(3), &mut tt, &StopFlag::new(), &mut |info| {
+            assert!(!info.pv.is_empty());
+            depths.push(info.depth);
+        });
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+    }
+
+    #[test]
+    fn node_limit() {
+        let result = best_move(&ChessBoard::new(), SearchLimits::nodes(500));
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 500);
+        assert!(result.depth >= 1);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{ChessBoard, Move, Piece, Square};
+use crate::eval::piece_value;
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+impl ChessBoard {
+    // Static exchange evaluation, the material won or lost on the target square when both sides keep
+    // recapturing with their least valuable piece and may stop whenever that is better for them
+    pub fn see(&self, m: &Move) -> i32 {
+        let mut occupied: u64 = self.white_pieces().value | self.black_pieces().value;
+        let mut white: bool = self.white_turn;
+
+        let mut gain: Vec<i32> = vec![piece_value(self.captured_piece(m))];
+        if self.get_square_piece(m.to) == Piece::None && self.captured_piece(m) == Piece::Pawn {
+            occupied &= !(1 << Square::new(m.from.row, m.to.column).to_i32());
+        }
+        let mut on_square: Piece = self.get_square_piece(m.from);
+        if m.promotion != Piece::None {
+            gain[0] += piece_value(m.promotion) - piece_value(Piece::Pawn);
+            on_square = m.promotion;
+        }
+        occupied &= !(1 << m.from.to_i32());
+        occupied |= 1 << m.to.to_i32();
+
+        loop {
+            white = !white;
+            let attacker: Square = match self.least_valuable_attacker(m.to, occupied, white) {
+                Some(square) => square,
+                None => break,
+            };
+            // The king can only take last, when nothing defends the square anymore
+            if self.get_square_piece(attacker) == Piece::King && self.least_valuable_attacker(m.to, occupied & !(1 << attacker.to_i32()), !white).is_some() {
+                break;
+            }
+            gain.push(piece_value(on_square) - gain[gain.len() - 1]);
+            on_square = self.get_square_piece(attacker);
+            occupied &= !(1 << attacker.to_i32());
+        }
+
+        while gain.len() > 1 {
+            let last: i32 = gain.pop().unwrap();
+            let previous: &mut i32 = gain.last_mut().unwrap();
+            *previous = -(-*previous).max(last);
+        }
+        gain[0]
+    }
+
+    pub fn see_ge(&self, m: &Move, threshold: i32) -> bool {
+        self.see(m) >= threshold
+    }
+
+    fn least_valuable_attacker(&self, target: Square, occupied: u64, white: bool) -> Option<Square> {
+        let own: u64 = occupied & if white { self.white_pieces().value } else { self.black_pieces().value };
+        let mut best: Option<(i32, Square)> = None;
+
+        let mut consider = |square: Square| {
+            if !square.is_valid() || own & (1 << square.to_i32()) == 0 { return; }
+            let value: i32 = piece_value(self.get_square_piece(square));
+            if best.is_some_and(|(v, _)| v <= value) { return; }
+            if self.is_pinned(square, target, occupied, white) { return; }
+            best = Some((value, square));
+        };
+
+        let pawn_row: i32 = if white { target.row - 1 } else { target.row + 1 };
+        for c in [target.column - 1, target.column + 1] {
+            let square: Square = Square::new(pawn_row, c);
+            if square.is_valid() && self.get_square_piece(square) == Piece::Pawn { consider(square); }
+        }
+        for (r, c) in KNIGHT_OFFSETS {
+            let square: Square = Square::new(target.row + r, target.column + c);
+            if square.is_valid() && self.get_square_piece(square) == Piece::Knight { consider(square); }
+        }
+        for (r, c) in KING_OFFSETS {
+            let diagonal: bool = r != 0 && c != 0;
+            // Pieces behind the ones already taken join in here, that is how x-rays are found
+            if let Some(square) = first_occupied(target, r, c, occupied) {
+                let piece: Piece = self.get_square_piece(square);
+                if piece == Piece::Queen || (diagonal && piece == Piece::Bishop) || (!diagonal && piece == Piece::Rook) {
+                    consider(square);
+                }
+            }
+            let square: Square = Square::new(target.row + r, target.column + c);
+            if square.is_valid() && self.get_square_piece(square) == Piece::King { consider(square); }
+        }
+        best.map(|(_, square)| square)
+    }
+
+    // Whether the piece on the square can not move to target without leaving its king in check
+    fn is_pinned(&self, square: Square, target: Square, occupied: u64, white: bool) -> bool {
+        let king_bb: u64 = occupied & if white { self.white_king.value } else { self.black_king.value };
+        if king_bb == 0 || square.to_i32() == king_bb.trailing_zeros() as i32 { return false; }
+        let king: Square = Square::new(king_bb.trailing_zeros() as i32 / 8, king_bb.trailing_zeros() as i32 % 8);
+
+        let (dr, dc): (i32, i32) = (square.row - king.row, square.column - king.column);
+        if !(dr == 0 || dc == 0 || dr.abs() == dc.abs()) { return false; }
+        let (r, c): (i32, i32) = (dr.signum(), dc.signum());
+        if first_occupied(king, r, c, occupied) != Some(square) { return false; }
+
+        let pinner: Square = match first_occupied(square, r, c, occupied) {
+            Some(pinner) => pinner,
+            None => return false,
+        };
+        let enemy: u64 = if white { self.black_pieces().value } else { self.white_pieces().value };
+        // Whatever stands on the target square is the piece being taken, taking the pinner is always fine
+        if enemy & (1 << pinner.to_i32()) == 0 || pinner == target { return false; }
+        let diagonal: bool = r != 0 && c != 0;
+        let piece: Piece = self.get_square_piece(pinner);
+        piece == Piece::Queen || (diagonal && piece == Piece::Bishop) || (!diagonal && piece == Piece::Rook)
+    }
+}
+
+fn first_occupied(from: Square, r: i32, c: i32, occupied: u64) -> Option<Square> {
+    let mut square: Square = Square::new(from.row + r, from.column + c);
+    while square.is_valid() {
+        if occupied & (1 << square.to_i32()) != 0 { return Some(square); }
+        square = Square::new(square.row + r, square.column + c);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_from_string;
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(square_from_string(from.to_string()), square_from_string(to.to_string()))
+    }
+
+    fn see(fen: &str, from: &str, to: &str) -> i32 {
+        ChessBoard::new_fen(fen).unwrap().see(&mv(from, to))
+    }
+
+    const P: i32 = 82;
+    const N: i32 = 337;
+    const B: i32 = 365;
+    const R: i32 = 477;
+    const Q: i32 = 1025;
+
+    #[test]
+    fn simple_exchanges() {
+        // Undefended pawn, pawn defended by a pawn, knight defended by a pawn
+        assert_eq!(see("4k3/8/8/4p3/8/8/8/4RK2 w - - 0 1", "E1", "E5"), P);
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4RK2 w - - 0 1", "E1", "E5"), P - R);
+        assert_eq!(see("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1", "D4", "E5"), N - P);
+        // Quiet move onto a square the opponent covers
+        assert_eq!(see("4k3/8/3p4/R7/8/8/8/4K3 w - - 0 1", "A5", "E5"), -R);
+        assert_eq!(see("4k3/8/3p4/R7/8/8/8/4K3 w - - 0 1", "A5", "A4"), 0);
+        // The king only joins in when nothing defends the square anymore
+        assert_eq!(see("8/8/8/8/8/4k3/3p4/3R3K w - - 0 1", "D1", "D2"), P - R);
+        assert_eq!(see("8/8/8/8/8/4k3/3p4/2QR3K w - - 0 1", "D1", "D2"), P);
+    }
+
+    #[test]
+    fn x_rays() {
+        // The second rook only joins once the first one has taken
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "D2", "D5"), P);
+        assert_eq!(see("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "D2", "D5"), P - R);
+        // A queen behind a bishop on the diagonal
+        assert_eq!(see("4k3/8/2n5/8/8/8/6B1/4K2Q w - - 0 1", "G2", "C6"), N);
+        assert_eq!(see("4k3/1p6/2n5/8/8/8/6B1/4K2Q w - - 0 1", "G2", "C6"), N - B + P);
+    }
+
+    #[test]
+    fn pins() {
+        // The f6 knight is pinned by the b2 bishop and can not take back on d5
+        assert_eq!(see("7k/8/5n2/3p4/8/8/1B6/3QK3 w - - 0 1", "D1", "D5"), P);
+        assert_eq!(see("7k/8/5n2/3p4/8/8/8/3QK3 w - - 0 1", "D1", "D5"), P - Q);
+        // A pinned piece may still take the piece pinning it
+        assert_eq!(see("7k/6b1/8/4p3/3B4/8/8/4K3 w - - 0 1", "D4", "E5"), P - B);
+        assert_eq!(see("7k/6b1/8/4p3/3B4/2Q5/8/4K3 w - - 0 1", "D4", "E5"), P);
+    }
+
+    #[test]
+    fn en_passant_and_promotion() {
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "E5", "D6"), P);
+        assert_eq!(see("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "E5", "D6"), 0);
+        // The d5 pawn leaves the file open for the rook behind it
+        assert_eq!(see("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 1", "E5", "D6"), P);
+
+        let b = ChessBoard::new_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let m = Move::new_promotion(square_from_string("A7".to_string()), square_from_string("B8".to_string()), Piece::Queen);
+        assert_eq!(b.see(&m), R + Q - P);
+        assert!(b.see_ge(&m, R));
+        assert!(!b.see_ge(&m, R + Q));
+        let m = Move::new_promotion(square_from_string("A7".to_string()), square_from_string("A8".to_string()), Piece::Queen);
+        assert_eq!(b.see(&m), -P);
+        assert!(b.see_ge(&m, -P));
+        assert!(!b.see_ge(&m, 0));
+    }
+}
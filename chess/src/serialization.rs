@@ -0,0 +1,78 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{parse_square, ChessBoard, Move, Square};
+
+// Squares, moves and boards are written the way people read them: e4, e7e8q and FEN
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string().to_lowercase())
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        let value: String = String::deserialize(deserializer)?;
+        parse_square(&value).ok_or_else(|| D::Error::custom(format!("invalid square {}", value)))
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_uci())
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        let value: String = String::deserialize(deserializer)?;
+        Move::from_uci(&value).ok_or_else(|| D::Error::custom(format!("invalid move {}", value)))
+    }
+}
+
+impl Serialize for ChessBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ChessBoard, D::Error> {
+        let value: String = String::deserialize(deserializer)?;
+        ChessBoard::new_fen(&value).map_err(|error| D::Error::custom(format!("invalid fen {}: {:?}", value, error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::PgnGame;
+    use crate::{ChessBoard, Color, Move, Piece, Square};
+
+    #[test]
+    fn json() {
+        assert_eq!(serde_json::to_string(&Square::new(3, 4)).unwrap(), "\"e4\"");
+        assert_eq!(serde_json::from_str::<Square>("\"E4\"").unwrap(), Square::new(3, 4));
+        assert!(serde_json::from_str::<Square>("\"i9\"").is_err());
+
+        let m = Move::from_uci("e7e8q").unwrap();
+        assert_eq!(serde_json::to_string(&m).unwrap(), "\"e7e8q\"");
+        assert_eq!(serde_json::from_str::<Move>("\"e7e8q\"").unwrap(), m);
+        assert!(serde_json::from_str::<Move>("\"e7\"").is_err());
+
+        assert_eq!(serde_json::to_string(&(Piece::Knight, Color::Black)).unwrap(), "[\"Knight\",\"Black\"]");
+        assert_eq!(serde_json::from_str::<Piece>("\"Queen\"").unwrap(), Piece::Queen);
+
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let json = serde_json::to_string(&ChessBoard::new_fen(fen).unwrap()).unwrap();
+        assert_eq!(json, format!("\"{}\"", fen));
+        assert_eq!(serde_json::from_str::<ChessBoard>(&json).unwrap().to_fen(), fen);
+        assert!(serde_json::from_str::<ChessBoard>("\"8/8 w\"").is_err());
+
+        let game = PgnGame::from_moves(&ChessBoard::new(), &[Move::from_uci("e2e4").unwrap()]);
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"moves\":[\"e4\"]"));
+        assert!(json.contains("[\"Event\",\"?\"]"));
+        assert_eq!(serde_json::from_str::<PgnGame>(&json).unwrap(), game);
+    }
+}
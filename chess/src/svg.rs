@@ -0,0 +1,369 @@
+use crate::{ChessBoard, Color, Move, Piece, Square, LIGHT_SQUARES};
+
+// Every square is drawn 45 units wide, the whole drawing is then scaled to the requested size
+const SQUARE: f64 = 45.0;
+const MARGIN: f64 = 15.0;
+const OVERLAY_OPACITY: f64 = 0.5;
+const ARROW_OPACITY: f64 = 0.8;
+
+// The pieces are plain shapes, so the diagram needs neither fonts nor images
+const KING: &str = r#"<path d="M 22.5 6 V 19 M 18 10.5 H 27" fill="none" stroke-width="2.5"/><path d="M 12 31 C 7 24 11 16 22.5 21 C 34 16 38 24 33 31 Z"/><path d="M 11 31 H 34 V 37 H 11 Z"/>"#;
+const QUEEN: &str = r#"<path d="M 11 31 L 8 14 L 15.5 24 L 16.5 11 L 22.5 23 L 28.5 11 L 29.5 24 L 37 14 L 34 31 Z"/><circle cx="8" cy="13" r="2.5"/><circle cx="16.5" cy="10" r="2.5"/><circle cx="28.5" cy="10" r="2.5"/><circle cx="37" cy="13" r="2.5"/><path d="M 11 31 H 34 V 37 H 11 Z"/>"#;
+const ROOK: &str = r#"<path d="M 10 37 H 35 V 33 H 31 V 17 H 34 V 9 H 29.5 V 12.5 H 25 V 9 H 20 V 12.5 H 15.5 V 9 H 11 V 17 H 14 V 33 H 10 Z"/>"#;
+const BISHOP: &str = r#"<circle cx="22.5" cy="8" r="2.5"/><path d="M 22.5 11 C 15 16 14 24 17 31 H 28 C 31 24 30 16 22.5 11 Z"/><path d="M 12 37 H 33 V 33 H 12 Z"/><path d="M 20 20.5 H 25 M 22.5 18 V 23" fill="none" stroke="{detail}"/>"#;
+const KNIGHT: &str = r#"<path d="M 13 37 H 34 C 35 26 32 15 24 11 L 22 7 L 19.5 11 C 15 13 10 19 10 24 L 12.5 27 L 19 23.5 C 19.5 27.5 13.5 31 13 37 Z"/><circle cx="18" cy="16" r="1.5" fill="{detail}" stroke="none"/>"#;
+const PAWN: &str = r#"<circle cx="22.5" cy="15" r="5.5"/><path d="M 18 21 H 27 L 30 33 H 15 Z"/><path d="M 12 37 H 33 V 33 H 12 Z"/>"#;
+
+// The coordinates are stroked too, every glyph fits a box 4 units wide and 6 high with the baseline at the bottom
+const GLYPH_SCALE: f64 = 1.5;
+const FILE_GLYPHS: [&str; 8] = [
+    "M 0.8 2 H 2.8 Q 3.5 2 3.5 2.8 V 6 M 3.5 3.8 H 1.6 Q 0.5 3.8 0.5 4.9 Q 0.5 6 1.6 6 H 3.5",
+    "M 0.5 0 V 6 M 0.5 3 Q 1 2 2.2 2 Q 3.5 2 3.5 4 Q 3.5 6 2.2 6 Q 1 6 0.5 5",
+    "M 3.5 2.5 Q 3 2 2 2 Q 0.5 2 0.5 4 Q 0.5 6 2 6 Q 3 6 3.5 5.5",
+    "M 3.5 0 V 6 M 3.5 3 Q 3 2 1.8 2 Q 0.5 2 0.5 4 Q 0.5 6 1.8 6 Q 3 6 3.5 5",
+    "M 0.5 4 H 3.5 Q 3.5 2 2 2 Q 0.5 2 0.5 4 Q 0.5 6 2 6 Q 3 6 3.5 5.5",
+    "M 3.2 0.3 Q 2.8 0 2.3 0 Q 1.5 0 1.5 1 V 6 M 0.5 2 H 3",
+    "M 3.5 2 V 6.5 Q 3.5 7.5 2 7.5 Q 1 7.5 0.7 7 M 3.5 3 Q 3 2 2 2 Q 0.5 2 0.5 3.8 Q 0.5 5.5 2 5.5 Q 3 5.5 3.5 4.5",
+    "M 0.5 0 V 6 M 0.5 3 Q 1 2 2.2 2 Q 3.5 2 3.5 3.2 V 6",
+];
+const RANK_GLYPHS: [&str; 8] = [
+    "M 1 1 L 2.2 0 V 6 M 1 6 H 3.4",
+    "M 0.5 1.2 Q 1 0 2 0 Q 3.5 0 3.5 1.6 Q 3.5 2.8 0.5 6 H 3.5",
+    "M 0.5 0.5 Q 1 0 2 0 Q 3.5 0 3.5 1.5 Q 3.5 3 1.7 3 Q 3.5 3 3.5 4.5 Q 3.5 6 2 6 Q 1 6 0.5 5.5",
+    "M 2.8 6 V 0 L 0.3 4.2 H 3.7",
+    "M 3.4 0 H 0.8 L 0.5 2.8 Q 1 2.3 2 2.3 Q 3.5 2.3 3.5 4.1 Q 3.5 6 2 6 Q 1 6 0.5 5.5",
+    "M 3.2 0.4 Q 2.8 0 2 0 Q 0.5 0 0.5 3.5 Q 0.5 6 2 6 Q 3.5 6 3.5 4.3 Q 3.5 2.6 2 2.6 Q 0.9 2.6 0.5 3.5",
+    "M 0.5 0 H 3.5 L 1.5 6",
+    "M 2 3 Q 0.7 3 0.7 1.5 Q 0.7 0 2 0 Q 3.3 0 3.3 1.5 Q 3.3 3 2 3 Q 0.5 3 0.5 4.5 Q 0.5 6 2 6 Q 3.5 6 3.5 4.5 Q 3.5 3 2 3 Z",
+];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Theme {
+    pub light_square: String,
+    pub dark_square: String,
+    pub last_move: String,
+    pub check: String,
+    pub highlight: String,
+    pub arrow: String,
+    pub coordinates: String,
+    pub background: String,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::brown()
+    }
+}
+
+impl Theme {
+    fn new(light: &str, dark: &str, last_move: &str) -> Theme {
+        Theme {
+            light_square: light.to_string(),
+            dark_square: dark.to_string(),
+            last_move: last_move.to_string(),
+            check: "#e0301e".to_string(),
+            highlight: "#3f8ad9".to_string(),
+            arrow: "#15781b".to_string(),
+            coordinates: "#e5e5e5".to_string(),
+            background: "#212121".to_string(),
+        }
+    }
+
+    pub fn brown() -> Theme {
+        Theme::new("#f0d9b5", "#b58863", "#cdd16a")
+    }
+
+    pub fn blue() -> Theme {
+        Theme::new("#dee3e6", "#8ca2ad", "#c3d888")
+    }
+
+    pub fn green() -> Theme {
+        Theme::new("#eeeed2", "#769656", "#f6f669")
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Arrow {
+    pub from: Square,
+    pub to: Square,
+    // The theme's arrow color when None
+    pub color: Option<String>,
+}
+
+impl Arrow {
+    pub fn new(from: Square, to: Square) -> Arrow {
+        Arrow { from, to, color: None }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SvgOptions {
+    // Width and height in pixels
+    pub size: u32,
+    // Black at the bottom
+    pub flipped: bool,
+    pub coordinates: bool,
+    pub theme: Theme,
+    pub last_move: Option<Move>,
+    // Marks the king of the side to move when it is in check
+    pub highlight_check: bool,
+    pub highlights: Vec<Square>,
+    pub arrows: Vec<Arrow>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            size: 400,
+            flipped: false,
+            coordinates: true,
+            theme: Theme::default(),
+            last_move: None,
+            highlight_check: true,
+            highlights: Vec::new(),
+            arrows: Vec::new(),
+        }
+    }
+}
+
+// Colors come from the caller, so they are escaped before going into an attribute
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn piece_id(piece: Piece, color: Color) -> String {
+    let name: &str = match piece {
+        Piece::King => "king",
+        Piece::Queen => "queen",
+        Piece::Rook => "rook",
+        Piece::Bishop => "bishop",
+        Piece::Knight => "knight",
+        Piece::Pawn => "pawn",
+        Piece::None => "none",
+    };
+    format!("{}-{}", if color == Color::White { "white" } else { "black" }, name)
+}
+
+fn piece_definition(piece: Piece, color: Color) -> String {
+    let shapes: &str = match piece {
+        Piece::King => KING,
+        Piece::Queen => QUEEN,
+        Piece::Rook => ROOK,
+        Piece::Bishop => BISHOP,
+        Piece::Knight => KNIGHT,
+        Piece::Pawn => PAWN,
+        Piece::None => "",
+    };
+    // Details on the pieces are drawn in the color of the other side
+    let (fill, detail): (&str, &str) = if color == Color::White { ("#ffffff", "#000000") } else { ("#000000", "#ffffff") };
+    format!("<g id=\"{}\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"1.5\" stroke-linejoin=\"round\" stroke-linecap=\"round\">{}</g>",
+        piece_id(piece, color), fill, shapes.replace("{detail}", detail))
+}
+
+// The top left corner of the square in drawing units
+fn square_position(square: Square, options: &SvgOptions) -> (f64, f64) {
+    let margin: f64 = if options.coordinates { MARGIN } else { 0.0 };
+    let (column, row): (i32, i32) = if options.flipped {
+        (7 - square.column, square.row)
+    } else {
+        (square.column, 7 - square.row)
+    };
+    (margin + column as f64 * SQUARE, margin + row as f64 * SQUARE)
+}
+
+fn square_rect(square: Square, options: &SvgOptions, fill: &str, opacity: Option<f64>) -> String {
+    let (x, y): (f64, f64) = square_position(square, options);
+    let opacity: String = opacity.map(|o| format!(" fill-opacity=\"{}\"", o)).unwrap_or_default();
+    format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"{}/>", x, y, SQUARE, SQUARE, escape(fill), opacity)
+}
+
+fn arrow(arrow: &Arrow, options: &SvgOptions) -> String {
+    let color: String = escape(arrow.color.as_deref().unwrap_or(&options.theme.arrow));
+    let (x1, y1): (f64, f64) = square_position(arrow.from, options);
+    let (x2, y2): (f64, f64) = square_position(arrow.to, options);
+    let (x1, y1, x2, y2): (f64, f64, f64, f64) = (x1 + SQUARE / 2.0, y1 + SQUARE / 2.0, x2 + SQUARE / 2.0, y2 + SQUARE / 2.0);
+
+    // An arrow from a square to itself is drawn as a ring around it
+    if arrow.from == arrow.to {
+        return format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" opacity=\"{}\"/>",
+            x1, y1, SQUARE / 2.0 - 3.0, color, SQUARE / 10.0, ARROW_OPACITY);
+    }
+
+    let head_length: f64 = SQUARE * 0.45;
+    let head_width: f64 = SQUARE * 0.5;
+    let length: f64 = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+    let (dx, dy): (f64, f64) = ((x2 - x1) / length, (y2 - y1) / length);
+    // The head ends a little before the center of the target square
+    let (tip_x, tip_y): (f64, f64) = (x2 - dx * SQUARE * 0.1, y2 - dy * SQUARE * 0.1);
+    let (base_x, base_y): (f64, f64) = (tip_x - dx * head_length, tip_y - dy * head_length);
+    let (left_x, left_y): (f64, f64) = (base_x - dy * head_width / 2.0, base_y + dx * head_width / 2.0);
+    let (right_x, right_y): (f64, f64) = (base_x + dy * head_width / 2.0, base_y - dx * head_width / 2.0);
+    format!("<g opacity=\"{op}\"><line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{c}\" stroke-width=\"{:.2}\" stroke-linecap=\"butt\"/>\
+        <polygon points=\"{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}\" fill=\"{c}\"/></g>",
+        x1, y1, base_x, base_y, SQUARE * 0.2, tip_x, tip_y, left_x, left_y, right_x, right_y, c = color, op = ARROW_OPACITY)
+}
+
+// A coordinate glyph centered on the point
+fn glyph(path: &str, x: f64, y: f64) -> String {
+    format!("<path transform=\"translate({} {}) scale({})\" d=\"{}\"/>", x - 2.0 * GLYPH_SCALE, y - 3.0 * GLYPH_SCALE, GLYPH_SCALE, path)
+}
+
+// The board as a standalone SVG document
+pub fn render(board: &ChessBoard, options: &SvgOptions) -> String {
+    let margin: f64 = if options.coordinates { MARGIN } else { 0.0 };
+    let width: f64 = SQUARE * 8.0 + margin * 2.0;
+    let mut svg: String = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+        version=\"1.1\" viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\">", width, width, options.size, options.size);
+
+    let squares: Vec<(Square, Piece, Color)> = (0..64).map(|i| {
+        let square: Square = Square::new(i / 8, i % 8);
+        (square, board.get_square_piece(square), board.get_square_color(square))
+    }).collect();
+
+    // Only the pieces that are on the board are defined
+    svg.push_str("<defs>");
+    let mut defined: Vec<String> = Vec::new();
+    for (_, piece, color) in squares.iter().filter(|(_, piece, _)| *piece != Piece::None) {
+        let id: String = piece_id(*piece, *color);
+        if !defined.contains(&id) {
+            svg.push_str(&piece_definition(*piece, *color));
+            defined.push(id);
+        }
+    }
+    svg.push_str("</defs>");
+
+    if options.coordinates {
+        svg.push_str(&format!("<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", width, width, escape(&options.theme.background)));
+    }
+    for (square, _, _) in squares.iter() {
+        let light: bool = LIGHT_SQUARES & (1 << square.to_i32()) != 0;
+        let fill: &str = if light { &options.theme.light_square } else { &options.theme.dark_square };
+        svg.push_str(&square_rect(*square, options, fill, None));
+    }
+    if let Some(m) = options.last_move {
+        svg.push_str(&square_rect(m.from, options, &options.theme.last_move, Some(OVERLAY_OPACITY * 1.5)));
+        svg.push_str(&square_rect(m.to, options, &options.theme.last_move, Some(OVERLAY_OPACITY * 1.5)));
+    }
+    for square in options.highlights.iter() {
+        svg.push_str(&square_rect(*square, options, &options.theme.highlight, Some(OVERLAY_OPACITY)));
+    }
+    if options.highlight_check {
+        let king: Option<Square> = if board.white_turn && board.is_white_checked() {
+            board.white_king.to_squares().first().copied()
+        } else if !board.white_turn && board.is_black_checked() {
+            board.black_king.to_squares().first().copied()
+        } else {
+            None
+        };
+        if let Some(king) = king {
+            svg.push_str(&square_rect(king, options, &options.theme.check, Some(OVERLAY_OPACITY * 1.5)));
+        }
+    }
+
+    if options.coordinates {
+        svg.push_str(&format!("<g fill=\"none\" stroke=\"{}\" stroke-width=\"0.8\" stroke-linecap=\"round\" stroke-linejoin=\"round\">",
+            escape(&options.theme.coordinates)));
+        for i in 0..8 {
+            let (x, _): (f64, f64) = square_position(Square::new(0, i), options);
+            let (_, y): (f64, f64) = square_position(Square::new(i, 0), options);
+            for center_y in [MARGIN / 2.0, width - MARGIN / 2.0] {
+                svg.push_str(&glyph(FILE_GLYPHS[i as usize], x + SQUARE / 2.0, center_y));
+            }
+            for center_x in [MARGIN / 2.0, width - MARGIN / 2.0] {
+                svg.push_str(&glyph(RANK_GLYPHS[i as usize], center_x, y + SQUARE / 2.0));
+            }
+        }
+        svg.push_str("</g>");
+    }
+
+    for (square, piece, color) in squares.iter().filter(|(_, piece, _)| *piece != Piece::None) {
+        let (x, y): (f64, f64) = square_position(*square, options);
+        svg.push_str(&format!("<use xlink:href=\"#{}\" href=\"#{}\" x=\"{}\" y=\"{}\"/>", piece_id(*piece, *color), piece_id(*piece, *color), x, y));
+    }
+    for a in options.arrows.iter() {
+        svg.push_str(&arrow(a, options));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_from_string;
+
+    fn sq(value: &str) -> Square {
+        square_from_string(value.to_string())
+    }
+
+    #[test]
+    fn pieces_and_squares() {
+        let svg = render(&ChessBoard::new(), &SvgOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("viewBox=\"0 0 390 390\" width=\"400\" height=\"400\""));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert_eq!(svg.matches("<g id=").count(), 12);
+        // Background and 64 squares, no highlights
+        assert_eq!(svg.matches("<rect ").count(), 65);
+        assert_eq!(svg.matches("<path transform=").count(), 32);
+        assert!(!svg.contains("font"));
+        assert!(!svg.contains("fill-opacity"));
+        // White's king on e1 and black's rook on a8
+        assert!(svg.contains("href=\"#white-king\" x=\"195\" y=\"330\""));
+        assert!(svg.contains("href=\"#black-rook\" x=\"15\" y=\"15\""));
+
+        let b = ChessBoard::new_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let svg = render(&b, &SvgOptions { coordinates: false, size: 200, ..SvgOptions::default() });
+        assert!(svg.contains("viewBox=\"0 0 360 360\" width=\"200\" height=\"200\""));
+        assert_eq!(svg.matches("<g id=").count(), 2);
+        assert_eq!(svg.matches("<path transform=").count(), 0);
+        assert!(svg.contains("href=\"#white-king\" x=\"180\" y=\"315\""));
+    }
+
+    #[test]
+    fn flipped_and_highlights() {
+        // After 1.f3 e5 2.g4 Qh4#
+        let b = ChessBoard::new_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let theme = Theme::green();
+        let options = SvgOptions {
+            flipped: true,
+            theme: theme.clone(),
+            last_move: Some(Move::new(sq("D8"), sq("H4"))),
+            highlights: vec![sq("F2")],
+            arrows: vec![Arrow::new(sq("G1"), sq("F3")), Arrow { from: sq("E1"), to: sq("E1"), color: Some("#ff0000".to_string()) }],
+            ..SvgOptions::default()
+        };
+        let svg = render(&b, &options);
+        // With black at the bottom e1 is in the top row, fourth from the left
+        assert!(svg.contains("href=\"#white-king\" x=\"150\" y=\"15\""));
+        assert!(svg.contains(&format!("<rect x=\"150\" y=\"15\" width=\"45\" height=\"45\" fill=\"{}\"", theme.check)));
+        assert!(svg.contains(&format!("<rect x=\"15\" y=\"150\" width=\"45\" height=\"45\" fill=\"{}\"", theme.last_move)));
+        assert!(svg.contains(&format!("<rect x=\"195\" y=\"15\" width=\"45\" height=\"45\" fill=\"{}\"", theme.light_square)));
+        assert!(svg.contains(&format!("fill=\"{}\" fill-opacity", theme.highlight)));
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert!(svg.contains(&format!("stroke=\"{}\"", theme.arrow)));
+        assert!(svg.contains("<circle cx=\"172.5\" cy=\"37.5\" r=\"19.5\" fill=\"none\" stroke=\"#ff0000\""));
+
+        let svg = render(&b, &SvgOptions { highlight_check: false, ..SvgOptions::default() });
+        assert!(!svg.contains(&Theme::default().check));
+    }
+
+    #[test]
+    fn escapes_colors() {
+        let theme = Theme { coordinates: "red\" onload=\"x".to_string(), ..Theme::blue() };
+        let options = SvgOptions {
+            theme,
+            arrows: vec![Arrow { from: sq("E2"), to: sq("E4"), color: Some("<blue>&'".to_string()) }],
+            ..SvgOptions::default()
+        };
+        let svg = render(&ChessBoard::new(), &options);
+        assert!(svg.contains("stroke=\"red&quot; onload=&quot;x\""));
+        assert!(svg.contains("stroke=\"&lt;blue&gt;&amp;&apos;\""));
+        assert!(!svg.contains("onload=\"x"));
+    }
+}
@@ -0,0 +1,1388 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::{ChessBoard, Move, Piece};
+
+const WDL_MAGIC: u32 = 0x5D23E871;
+const DTZ_MAGIC: u32 = 0x88AC504B;
+const WDL_SUFFIX: &str = ".rtbw";
+const DTZ_SUFFIX: &str = ".rtbz";
+// The table files use these letters for both colors, the pieces before the v are the stronger side
+const PIECE_LETTERS: &str = " PNBRQK";
+const MAX_TABLE_PIECES: usize = 7;
+
+// Index tables of the Syzygy encoding, positions are reduced by symmetry before they are numbered
+const OFF_DIAG: [i8; 64] = [
+     0, -1, -1, -1, -1, -1, -1, -1,
+     1,  0, -1, -1, -1, -1, -1, -1,
+     1,  1,  0, -1, -1, -1, -1, -1,
+     1,  1,  1,  0, -1, -1, -1, -1,
+     1,  1,  1,  1,  0, -1, -1, -1,
+     1,  1,  1,  1,  1,  0, -1, -1,
+     1,  1,  1,  1,  1,  1,  0, -1,
+     1,  1,  1,  1,  1,  1,  1,  0,
+];
+
+const TRIANGLE: [u8; 64] = [
+     6,  0,  1,  2,  2,  1,  0,  6,
+     0,  7,  3,  4,  4,  3,  7,  0,
+     1,  3,  8,  5,  5,  8,  3,  1,
+     2,  4,  5,  9,  9,  5,  4,  2,
+     2,  4,  5,  9,  9,  5,  4,  2,
+     1,  3,  8,  5,  5,  8,  3,  1,
+     0,  7,  3,  4,  4,  3,  7,  0,
+     6,  0,  1,  2,  2,  1,  0,  6,
+];
+
+const LOWER: [u8; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+const DIAG: [u8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  8,
+     0,  1,  0,  0,  0,  0,  9,  0,
+     0,  0,  2,  0,  0, 10,  0,  0,
+     0,  0,  0,  3, 11,  0,  0,  0,
+     0,  0,  0, 12,  4,  0,  0,  0,
+     0,  0, 13,  0,  0,  5,  0,  0,
+     0, 14,  0,  0,  0,  0,  6,  0,
+    15,  0,  0,  0,  0,  0,  0,  7,
+];
+
+const FLAP: [u8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  6, 12, 18, 18, 12,  6,  0,
+     1,  7, 13, 19, 19, 13,  7,  1,
+     2,  8, 14, 20, 20, 14,  8,  2,
+     3,  9, 15, 21, 21, 15,  9,  3,
+     4, 10, 16, 22, 22, 16, 10,  4,
+     5, 11, 17, 23, 23, 17, 11,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const PAWN_TWIST: [u8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    47, 35, 23, 11, 10, 22, 34, 46,
+    45, 33, 21,  9,  8, 20, 32, 44,
+    43, 31, 19,  7,  6, 18, 30, 42,
+    41, 29, 17,  5,  4, 16, 28, 40,
+    39, 27, 15,  3,  2, 14, 26, 38,
+    37, 25, 13,  1,  0, 12, 24, 36,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const KK_IDX: [[i16; 64]; 10] = [
+    [
+         -1,  -1,  -1,   0,   1,   2,   3,   4,
+         -1,  -1,  -1,   5,   6,   7,   8,   9,
+         10,  11,  12,  13,  14,  15,  16,  17,
+         18,  19,  20,  21,  22,  23,  24,  25,
+         26,  27,  28,  29,  30,  31,  32,  33,
+         34,  35,  36,  37,  38,  39,  40,  41,
+         42,  43,  44,  45,  46,  47,  48,  49,
+         50,  51,  52,  53,  54,  55,  56,  57,
+    ],
+    [
+         58,  -1,  -1,  -1,  59,  60,  61,  62,
+         63,  -1,  -1,  -1,  64,  65,  66,  67,
+         68,  69,  70,  71,  72,  73,  74,  75,
+         76,  77,  78,  79,  80,  81,  82,  83,
+         84,  85,  86,  87,  88,  89,  90,  91,
+         92,  93,  94,  95,  96,  97,  98,  99,
+        100, 101, 102, 103, 104, 105, 106, 107,
+        108, 109, 110, 111, 112, 113, 114, 115,
+    ],
+    [
+        116, 117,  -1,  -1,  -1, 118, 119, 120,
+        121, 122,  -1,  -1,  -1, 123, 124, 125,
+        126, 127, 128, 129, 130, 131, 132, 133,
+        134, 135, 136, 137, 138, 139, 140, 141,
+        142, 143, 144, 145, 146, 147, 148, 149,
+        150, 151, 152, 153, 154, 155, 156, 157,
+        158, 159, 160, 161, 162, 163, 164, 165,
+        166, 167, 168, 169, 170, 171, 172, 173,
+    ],
+    [
+        174,  -1,  -1,  -1, 175, 176, 177, 178,
+        179,  -1,  -1,  -1, 180, 181, 182, 183,
+        184,  -1,  -1,  -1, 185, 186, 187, 188,
+        189, 190, 191, 192, 193, 194, 195, 196,
+        197, 198, 199, 200, 201, 202, 203, 204,
+        205, 206, 207, 208, 209, 210, 211, 212,
+        213, 214, 215, 216, 217, 218, 219, 220,
+        221, 222, 223, 224, 225, 226, 227, 228,
+    ],
+    [
+        229, 230,  -1,  -1,  -1, 231, 232, 233,
+        234, 235,  -1,  -1,  -1, 236, 237, 238,
+        239, 240,  -1,  -1,  -1, 241, 242, 243,
+        244, 245, 246, 247, 248, 249, 250, 251,
+        252, 253, 254, 255, 256, 257, 258, 259,
+        260, 261, 262, 263, 264, 265, 266, 267,
+        268, 269, 270, 271, 272, 273, 274, 275,
+        276, 277, 278, 279, 280, 281, 282, 283,
+    ],
+    [
+        284, 285, 286, 287, 288, 289, 290, 291,
+        292, 293,  -1,  -1,  -1, 294, 295, 296,
+        297, 298,  -1,  -1,  -1, 299, 300, 301,
+        302, 303,  -1,  -1,  -1, 304, 305, 306,
+        307, 308, 309, 310, 311, 312, 313, 314,
+        315, 316, 317, 318, 319, 320, 321, 322,
+        323, 324, 325, 326, 327, 328, 329, 330,
+        331, 332, 333, 334, 335, 336, 337, 338,
+    ],
+    [
+         -1,  -1, 339, 340, 341, 342, 343, 344,
+         -1,  -1, 345, 346, 347, 348, 349, 350,
+         -1,  -1, 441, 351, 352, 353, 354, 355,
+         -1,  -1,  -1, 442, 356, 357, 358, 359,
+         -1,  -1,  -1,  -1, 443, 360, 361, 362,
+         -1,  -1,  -1,  -1,  -1, 444, 363, 364,
+         -1,  -1,  -1,  -1,  -1,  -1, 445, 365,
+         -1,  -1,  -1,  -1,  -1,  -1,  -1, 446,
+    ],
+    [
+         -1,  -1,  -1, 366, 367, 368, 369, 370,
+         -1,  -1,  -1, 371, 372, 373, 374, 375,
+         -1,  -1,  -1, 376, 377, 378, 379, 380,
+         -1,  -1,  -1, 447, 381, 382, 383, 384,
+         -1,  -1,  -1,  -1, 448, 385, 386, 387,
+         -1,  -1,  -1,  -1,  -1, 449, 388, 389,
+         -1,  -1,  -1,  -1,  -1,  -1, 450, 390,
+         -1,  -1,  -1,  -1,  -1,  -1,  -1, 451,
+    ],
+    [
+        452, 391, 392, 393, 394, 395, 396, 397,
+         -1,  -1,  -1,  -1, 398, 399, 400, 401,
+         -1,  -1,  -1,  -1, 402, 403, 404, 405,
+         -1,  -1,  -1,  -1, 406, 407, 408, 409,
+         -1,  -1,  -1,  -1, 453, 410, 411, 412,
+         -1,  -1,  -1,  -1,  -1, 454, 413, 414,
+         -1,  -1,  -1,  -1,  -1,  -1, 455, 415,
+         -1,  -1,  -1,  -1,  -1,  -1,  -1, 456,
+    ],
+    [
+        457, 416, 417, 418, 419, 420, 421, 422,
+         -1, 458, 423, 424, 425, 426, 427, 428,
+         -1,  -1,  -1,  -1,  -1, 429, 430, 431,
+         -1,  -1,  -1,  -1,  -1, 432, 433, 434,
+         -1,  -1,  -1,  -1,  -1, 435, 436, 437,
+         -1,  -1,  -1,  -1,  -1, 459, 438, 439,
+         -1,  -1,  -1,  -1,  -1,  -1, 460, 440,
+         -1,  -1,  -1,  -1,  -1,  -1,  -1, 461,
+    ],
+];
+
+
+const FILE_TO_FILE: [usize; 8] = [0, 1, 2, 3, 3, 2, 1, 0];
+const WDL_TO_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+const PA_FLAGS: [u8; 5] = [8, 0, 0, 0, 4];
+const WDL_TO_DTZ: [i32; 5] = [-1, -101, 0, 101, 1];
+
+const fn binomials() -> [[u64; 64]; 7] {
+    let mut table: [[u64; 64]; 7] = [[0; 64]; 7];
+    let mut i: usize = 0;
+    while i < 7 {
+        let mut j: usize = 0;
+        while j < 64 {
+            let mut f: u64 = 1;
+            let mut l: u64 = 1;
+            let mut k: usize = 0;
+            while k < i {
+                f *= j.wrapping_sub(k) as u64;
+                l *= k as u64 + 1;
+                k += 1;
+            }
+            table[i][j] = f / l;
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+const BINOMIAL: [[u64; 64]; 7] = binomials();
+
+// Index of the leading pawn per file half and number of pawns, and the number of indices per file
+const fn pawn_indices() -> ([[u64; 24]; 6], [[u64; 4]; 6]) {
+    let mut index: [[u64; 24]; 6] = [[0; 24]; 6];
+    let mut factor: [[u64; 4]; 6] = [[0; 4]; 6];
+    let mut i: usize = 0;
+    while i < 6 {
+        let mut s: u64 = 0;
+        let mut j: usize = 0;
+        while j < 24 {
+            index[i][j] = s;
+            s += BINOMIAL[i][PAWN_TWIST[(1 + j % 6) * 8 + j / 6] as usize];
+            if (j + 1).is_multiple_of(6) {
+                factor[i][j / 6] = s;
+                s = 0;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    (index, factor)
+}
+
+const PAWN_INDICES: ([[u64; 24]; 6], [[u64; 4]; 6]) = pawn_indices();
+
+// Win, draw or loss for the side to move, cursed wins and blessed losses are decided by the fifty move rule
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+
+    // The outcome with the halfmove clock taken into account, dtz counts plies until the next capture or pawn move
+    fn from_dtz(halfmove_clock: u32, dtz: i32) -> Wdl {
+        if dtz > 0 {
+            if dtz + halfmove_clock as i32 <= 100 { Wdl::Win } else { Wdl::CursedWin }
+        } else if dtz < 0 {
+            if -dtz + halfmove_clock as i32 <= 100 { Wdl::Loss } else { Wdl::BlessedLoss }
+        } else {
+            Wdl::Draw
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SyzygyError {
+    Io(String),
+}
+
+// A legal move of the probed position, wdl and dtz are seen from the side that plays it
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RootMove {
+    pub m: Move,
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+// A DTZ table only holds one side to move, the other side is found by looking one move ahead
+enum TableValue {
+    Value(i32),
+    OtherSide,
+}
+
+// Piece counts by the files' piece codes: 1 to 6 for white pawn to king, 9 to 14 for black
+type Material = [u8; 16];
+
+fn material_key(material: &Material, mirror: bool) -> u64 {
+    let mut key: u64 = 0;
+    for piece in 1..6 {
+        let (white, black): (u8, u8) = if mirror { (material[piece + 8], material[piece]) } else { (material[piece], material[piece + 8]) };
+        key |= (white as u64) << (piece * 4) | (black as u64) << (32 + piece * 4);
+    }
+    key
+}
+
+// Table names look like KRPvKR
+fn parse_material(name: &str) -> Option<Material> {
+    let (white, black): (&str, &str) = name.split_once('v')?;
+    let mut material: Material = [0; 16];
+    for (side, offset) in [(white, 0), (black, 8)] {
+        for c in side.chars() {
+            let piece: usize = PIECE_LETTERS.find(c).filter(|p| *p > 0)?;
+            material[piece + offset] += 1;
+        }
+        if material[6 + offset] != 1 { return None; }
+    }
+    let pieces: usize = material.iter().map(|n| *n as usize).sum();
+    if pieces > MAX_TABLE_PIECES { return None; }
+    Some(material)
+}
+
+fn piece_bits(board: &ChessBoard, white: bool, piece: u8) -> u64 {
+    let bb = match (white, piece) {
+        (true, 1) => board.white_pawn,
+        (true, 2) => board.white_knight,
+        (true, 3) => board.white_bishop,
+        (true, 4) => board.white_rook,
+        (true, 5) => board.white_queen,
+        (true, _) => board.white_king,
+        (false, 1) => board.black_pawn,
+        (false, 2) => board.black_knight,
+        (false, 3) => board.black_bishop,
+        (false, 4) => board.black_rook,
+        (false, 5) => board.black_queen,
+        (false, _) => board.black_king,
+    };
+    bb.value
+}
+
+fn board_material(board: &ChessBoard) -> Material {
+    let mut material: Material = [0; 16];
+    for piece in 1..7u8 {
+        material[piece as usize] = piece_bits(board, true, piece).count_ones() as u8;
+        material[piece as usize + 8] = piece_bits(board, false, piece).count_ones() as u8;
+    }
+    material
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([byte(data, at), byte(data, at + 1)])
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([byte(data, at), byte(data, at + 1), byte(data, at + 2), byte(data, at + 3)])
+}
+
+// Reads past the end of a damaged file give zeros instead of a panic
+fn byte(data: &[u8], at: usize) -> u8 {
+    data.get(at).copied().unwrap_or(0)
+}
+
+fn subfactor(k: u64, n: u64) -> u64 {
+    let mut f: u64 = n;
+    let mut l: u64 = 1;
+    for i in 1..k {
+        f *= n - i;
+        l *= i + 1;
+    }
+    f / l
+}
+
+// The canonical Huffman code of one table, with the offsets of its parts in the file
+#[derive(Default)]
+struct PairsData {
+    index_bits: u32,
+    block_size: u32,
+    min_length: usize,
+    const_value: [u8; 2],
+    offsets: usize,
+    symbol_patterns: usize,
+    symbol_lengths: Vec<u8>,
+    base: Vec<u64>,
+    // Bytes of the index, size and data parts
+    sizes: [u64; 3],
+    index_table: usize,
+    size_table: usize,
+    data: usize,
+}
+
+#[derive(Default)]
+struct Encoding {
+    pieces: [u8; MAX_TABLE_PIECES],
+    norm: [u8; MAX_TABLE_PIECES],
+    factor: [u64; MAX_TABLE_PIECES],
+    // Number of indices, the product of the factors
+    positions: u64,
+    pairs: PairsData,
+}
+
+// A loaded .rtbw or .rtbz file
+struct Table {
+    data: Vec<u8>,
+    // Per side to move and leading pawn file, DTZ and symmetric tables only have the first side
+    encodings: [Vec<Encoding>; 2],
+    dtz_flags: [u8; 4],
+    dtz_map: usize,
+    dtz_map_index: [[usize; 4]; 4],
+}
+
+// One material combination, its files are only read when a position needs them
+struct TableFiles {
+    name: String,
+    directory: PathBuf,
+    key: u64,
+    symmetric: bool,
+    pieces: usize,
+    has_pawns: bool,
+    // Both kings are the only unique pieces, so they are numbered together
+    kk_encoding: bool,
+    // The leading pawns first
+    pawns: [u8; 2],
+    has_dtz: bool,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+pub struct Tablebase {
+    files: Vec<TableFiles>,
+    // Both colorings of every material combination
+    keys: HashMap<u64, usize>,
+    max_pieces: u32,
+}
+
+impl Tablebase {
+    // Finds the .rtbw files of the directory, with the .rtbz files next to them
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Tablebase, SyzygyError> {
+        let directory: &Path = directory.as_ref();
+        let entries = fs::read_dir(directory).map_err(|e| SyzygyError::Io(e.to_string()))?;
+        let mut names: Vec<String> = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| SyzygyError::Io(e.to_string()))?;
+            if let Some(name) = entry.file_name().to_str().and_then(|n| n.strip_suffix(WDL_SUFFIX)) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        let mut tablebase: Tablebase = Tablebase { files: Vec::new(), keys: HashMap::new(), max_pieces: 0 };
+        for name in names {
+            let material: Material = match parse_material(&name) {
+                Some(material) => material,
+                None => continue,
+            };
+            let key: u64 = material_key(&material, false);
+            if tablebase.keys.contains_key(&key) { continue; }
+            let mirrored_key: u64 = material_key(&material, true);
+            let pieces: usize = material.iter().map(|n| *n as usize).sum();
+            let has_pawns: bool = material[1] > 0 || material[9] > 0;
+            // The side with fewer pawns leads, a side without pawns never does
+            let mut pawns: [u8; 2] = [material[1], material[9]];
+            if pawns[1] > 0 && (pawns[0] == 0 || pawns[0] > pawns[1]) { pawns.swap(0, 1); }
+            let files: TableFiles = TableFiles {
+                has_dtz: directory.join(format!("{}{}", name, DTZ_SUFFIX)).is_file(),
+                name,
+                directory: directory.to_path_buf(),
+                key,
+                symmetric: key == mirrored_key,
+                pieces,
+                has_pawns,
+                kk_encoding: material.iter().filter(|n| **n == 1).count() == 2,
+                pawns,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            };
+            tablebase.keys.insert(key, tablebase.files.len());
+            tablebase.keys.insert(mirrored_key, tablebase.files.len());
+            tablebase.max_pieces = tablebase.max_pieces.max(pieces as u32);
+            tablebase.files.push(files);
+        }
+        Ok(tablebase)
+    }
+
+    // Number of WDL tables found
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn max_pieces(&self) -> u32 {
+        self.max_pieces
+    }
+
+    // Positions with castling rights or more pieces than the largest table are not in the tables
+    pub fn can_probe(&self, board: &ChessBoard) -> bool {
+        let pieces: u32 = board.white_pieces().count() + board.black_pieces().count();
+        board.castling_rights.value == 0 && pieces <= self.max_pieces
+    }
+
+    // The outcome with the halfmove clock at zero, as if the last move was a capture or pawn move
+    pub fn probe_wdl(&self, board: &ChessBoard) -> Option<Wdl> {
+        if !self.can_probe(board) { return None; }
+        self.wdl(board).map(|(value, _)| Wdl::from_value(value))
+    }
+
+    // Plies until a capture or pawn move that keeps the result, negative when losing and zero for draws
+    pub fn probe_dtz(&self, board: &ChessBoard) -> Option<i32> {
+        if !self.can_probe(board) { return None; }
+        self.dtz(board)
+    }
+
+    // Every legal move with the outcome it leads to, counting the plies already played towards the fifty move rule
+    pub fn root_moves(&self, board: &ChessBoard) -> Option<Vec<RootMove>> {
+        if !self.can_probe(board) { return None; }
+        let mut moves: Vec<RootMove> = Vec::new();
+        for m in board.generate_moves() {
+            let mut child: ChessBoard = *board;
+            child.make_move(m, false);
+            let dtz: i32 = if is_mated(&child) {
+                1
+            } else if child.halfmove_clock > 0 {
+                let dtz: i32 = -self.dtz(&child)?;
+                dtz + dtz.signum()
+            } else {
+                WDL_TO_DTZ[(2 - self.wdl(&child)?.0) as usize]
+            };
+            moves.push(RootMove { m, wdl: Wdl::from_dtz(board.halfmove_clock, dtz), dtz });
+        }
+        Some(moves)
+    }
+
+    // The quickest win, a draw if there is none, otherwise the slowest loss
+    pub fn best_move(&self, board: &ChessBoard) -> Option<RootMove> {
+        let moves: Vec<RootMove> = self.root_moves(board)?;
+        let win: Option<&RootMove> = moves.iter().filter(|r| r.dtz > 0).min_by_key(|r| r.dtz);
+        let draw: Option<&RootMove> = moves.iter().find(|r| r.dtz == 0);
+        let loss: Option<&RootMove> = moves.iter().filter(|r| r.dtz < 0).min_by_key(|r| r.dtz);
+        win.or(draw).or(loss).copied()
+    }
+
+    // The stored value of the position, or None if its table is missing or broken
+    fn probe_table(&self, board: &ChessBoard, wdl: i32, kind: TableKind) -> Option<TableValue> {
+        let material: Material = board_material(board);
+        let key: u64 = material_key(&material, false);
+        // Two bare kings are a draw without a table
+        if key == 0 && kind == TableKind::Wdl { return Some(TableValue::Value(0)); }
+        let files: &TableFiles = &self.files[*self.keys.get(&key)?];
+        let table: &Table = files.table(kind)?;
+
+        // The table is stored with its stronger side as white, otherwise the board is looked at upside down
+        let (flip, side): (bool, usize) = if files.symmetric {
+            (!board.white_turn, 0)
+        } else {
+            let flip: bool = key != files.key;
+            (flip, (board.white_turn == flip) as usize)
+        };
+        let mut squares: [usize; MAX_TABLE_PIECES] = [0; MAX_TABLE_PIECES];
+        let mut file: usize = 0;
+        let mut flags: u8 = 0;
+        let encoding: &Encoding;
+        let index: u64;
+        if !files.has_pawns {
+            if kind == TableKind::Dtz {
+                flags = table.dtz_flags[0];
+                if (flags & 1) as usize != side && !files.symmetric { return Some(TableValue::OtherSide); }
+            }
+            encoding = table.encodings[if kind == TableKind::Dtz { 0 } else { side }].first()?;
+            let mut i: usize = 0;
+            while i < files.pieces {
+                i = fill_squares(board, &encoding.pieces, flip, 0, &mut squares, i);
+            }
+            index = encode(&mut squares, encoding, files);
+        } else {
+            let mirror: usize = if flip { 0x38 } else { 0 };
+            let mut i: usize = fill_squares(board, &table.encodings[0].first()?.pieces, flip, mirror, &mut squares, 0);
+            file = leading_pawn(&mut squares, files);
+            if kind == TableKind::Dtz {
+                flags = table.dtz_flags[file];
+                if (flags & 1) as usize != side && !files.symmetric { return Some(TableValue::OtherSide); }
+            }
+            encoding = table.encodings[if kind == TableKind::Dtz { 0 } else { side }].get(file)?;
+            while i < files.pieces {
+                i = fill_squares(board, &encoding.pieces, flip, mirror, &mut squares, i);
+            }
+            index = encode(&mut squares, encoding, files);
+        }
+
+        let (first, second): (u8, u8) = table.decompress(&encoding.pairs, index);
+        if kind == TableKind::Wdl { return Some(TableValue::Value(first as i32 - 2)); }
+        let mut value: usize = first as usize + (((second & 0xF) as usize) << 8);
+        if flags & 2 != 0 {
+            let map: usize = table.dtz_map + table.dtz_map_index[file][WDL_TO_MAP[(wdl + 2) as usize]];
+            value = if flags & 16 == 0 { byte(&table.data, map + value) as usize } else { read_u16(&table.data, map + 2 * value) as usize };
+        }
+        if flags & PA_FLAGS[(wdl + 2) as usize] == 0 || wdl & 1 != 0 { value *= 2; }
+        Some(TableValue::Value(value as i32))
+    }
+
+    fn wdl_table(&self, board: &ChessBoard) -> Option<i32> {
+        match self.probe_table(board, 0, TableKind::Wdl)? {
+            TableValue::Value(value) => Some(value),
+            TableValue::OtherSide => None,
+        }
+    }
+
+    // Captures are tried first, the tables leave out positions where a capture is the best move
+    fn alpha_beta(&self, board: &ChessBoard, mut alpha: i32, beta: i32) -> Option<i32> {
+        for m in board.generate_captures().iter().filter(|m| board.is_capture(m)) {
+            let mut child: ChessBoard = *board;
+            child.make_move(*m, false);
+            let value: i32 = -self.alpha_beta(&child, -beta, -alpha)?;
+            if value > alpha {
+                if value >= beta { return Some(value); }
+                alpha = value;
+            }
+        }
+        Some(alpha.max(self.wdl_table(board)?))
+    }
+
+    // The value from -2 to 2, and whether the best move found is a winning capture
+    fn wdl(&self, board: &ChessBoard) -> Option<(i32, bool)> {
+        let mut best_capture: i32 = -3;
+        let mut best_en_passant: i32 = -3;
+        for m in board.generate_captures().iter().filter(|m| board.is_capture(m)) {
+            let mut child: ChessBoard = *board;
+            child.make_move(*m, false);
+            let value: i32 = -self.alpha_beta(&child, -2, -best_capture)?;
+            if value > best_capture {
+                if value == 2 { return Some((2, true)); }
+                if !is_en_passant(board, m) {
+                    best_capture = value;
+                } else if value > best_en_passant {
+                    best_en_passant = value;
+                }
+            }
+        }
+
+        let value: i32 = self.wdl_table(board)?;
+        // The tables assume en passant is not possible, so it counts unless it is the only move
+        if best_en_passant > best_capture {
+            if best_en_passant > value { return Some((best_en_passant, true)); }
+            best_capture = best_en_passant;
+        }
+        if best_capture >= value { return Some((best_capture, best_capture > 0)); }
+        if best_en_passant > -3 && value == 0 {
+            let only_en_passant: bool = board.generate_moves().iter().all(|m| is_en_passant(board, m));
+            if only_en_passant && !is_checked(board) { return Some((best_en_passant, true)); }
+        }
+        Some((value, false))
+    }
+
+    fn dtz(&self, board: &ChessBoard) -> Option<i32> {
+        let (wdl, zeroing): (i32, bool) = self.wdl(board)?;
+        if wdl == 0 { return Some(0); }
+        if zeroing { return Some(WDL_TO_DTZ[(wdl + 2) as usize]); }
+
+        let moves: Vec<Move> = board.generate_moves();
+        // A pawn move that keeps the win resets the clock at once
+        if wdl > 0 {
+            for m in moves.iter().filter(|m| is_pawn_move(board, m) && !board.is_capture(m)) {
+                let mut child: ChessBoard = *board;
+                child.make_move(*m, false);
+                if -self.wdl(&child)?.0 == wdl { return Some(WDL_TO_DTZ[(wdl + 2) as usize]); }
+            }
+        }
+
+        match self.probe_table(board, wdl, TableKind::Dtz) {
+            Some(TableValue::Value(dtz)) => return Some(WDL_TO_DTZ[(wdl + 2) as usize] + if wdl > 0 { dtz } else { -dtz }),
+            Some(TableValue::OtherSide) => (),
+            None => return None,
+        }
+
+        // The table has the other side to move, so one move is looked ahead
+        let mut best: i32 = if wdl > 0 { i32::MAX } else { WDL_TO_DTZ[(wdl + 2) as usize] };
+        for m in moves.iter().filter(|m| !is_pawn_move(board, m) && !board.is_capture(m)) {
+            let mut child: ChessBoard = *board;
+            child.make_move(*m, false);
+            let value: i32 = -self.dtz(&child)?;
+            if value == 1 && is_mated(&child) {
+                best = 1;
+            } else if wdl > 0 {
+                if value > 0 && value + 1 < best { best = value + 1; }
+            } else if value - 1 < best {
+                best = value - 1;
+            }
+        }
+        Some(best)
+    }
+}
+
+impl TableFiles {
+    fn table(&self, kind: TableKind) -> Option<&Table> {
+        let (cell, suffix): (&OnceLock<Option<Table>>, &str) = match kind {
+            TableKind::Wdl => (&self.wdl, WDL_SUFFIX),
+            TableKind::Dtz if self.has_dtz => (&self.dtz, DTZ_SUFFIX),
+            TableKind::Dtz => return None,
+        };
+        cell.get_or_init(|| {
+            let data: Vec<u8> = fs::read(self.directory.join(format!("{}{}", self.name, suffix))).ok()?;
+            Table::new(data, self, kind)
+        }).as_ref()
+    }
+}
+
+impl Table {
+    fn new(data: Vec<u8>, files: &TableFiles, kind: TableKind) -> Option<Table> {
+        // Complete files are 16 bytes longer than a multiple of 64
+        if data.len() % 64 != 16 { return None; }
+        let magic: u32 = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if read_u32(&data, 0) != magic { return None; }
+
+        let split: bool = kind == TableKind::Wdl && data[4] & 1 != 0;
+        let count: usize = if files.has_pawns { 4 } else { 1 };
+        let mut table: Table = Table { data: Vec::new(), encodings: [Vec::new(), Vec::new()], dtz_flags: [0; 4], dtz_map: 0, dtz_map_index: [[0; 4]; 4] };
+        let mut at: usize = 5;
+        let header_size: usize = files.pieces + 1 + (files.has_pawns && files.pawns[1] > 0) as usize;
+        let sides: usize = if split { 2 } else { 1 };
+        for file in 0..count {
+            let header: &[u8] = data.get(at..at + header_size)?;
+            for (side, encodings) in table.encodings.iter_mut().enumerate().take(sides) {
+                encodings.push(Encoding::new(header, 4 * side as u32, file, files));
+            }
+            at += header_size;
+        }
+        at += at & 1;
+
+        // The parts of all tables follow each other in this order
+        let layout: Vec<(usize, usize)> = (0..count).flat_map(|file| (0..sides).map(move |side| (side, file))).collect();
+        for (side, file) in layout.iter() {
+            let encoding: &mut Encoding = &mut table.encodings[*side][*file];
+            let (pairs, flags) = PairsData::new(&data, &mut at, encoding.positions, kind)?;
+            encoding.pairs = pairs;
+            if kind == TableKind::Dtz { table.dtz_flags[*file] = flags; }
+        }
+
+        // DTZ values are stored as indices into small maps, one per outcome
+        if kind == TableKind::Dtz {
+            table.dtz_map = at;
+            for (flags, map_index) in table.dtz_flags.iter().zip(table.dtz_map_index.iter_mut()).take(count) {
+                if flags & 2 == 0 { continue; }
+                if flags & 16 == 0 {
+                    for index in map_index.iter_mut() {
+                        *index = at + 1 - table.dtz_map;
+                        at += 1 + byte(&data, at) as usize;
+                    }
+                } else {
+                    at += at & 1;
+                    for index in map_index.iter_mut() {
+                        *index = at + 2 - table.dtz_map;
+                        at += 2 + 2 * read_u16(&data, at) as usize;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for (side, file) in layout.iter() {
+            let pairs: &mut PairsData = &mut table.encodings[*side][*file].pairs;
+            pairs.index_table = at;
+            at += pairs.sizes[0] as usize;
+        }
+        for (side, file) in layout.iter() {
+            let pairs: &mut PairsData = &mut table.encodings[*side][*file].pairs;
+            pairs.size_table = at;
+            at += pairs.sizes[1] as usize;
+        }
+        for (side, file) in layout.iter() {
+            let pairs: &mut PairsData = &mut table.encodings[*side][*file].pairs;
+            // Tables with a single value have no data, and nothing to align
+            if pairs.sizes[2] > 0 { at = (at + 63) & !63; }
+            pairs.data = at;
+            at += pairs.sizes[2] as usize;
+        }
+        if at > data.len() { return None; }
+        table.data = data;
+        Some(table)
+    }
+
+    // The symbol at the index, the first two bytes of its pattern
+    fn decompress(&self, pairs: &PairsData, index: u64) -> (u8, u8) {
+        if pairs.index_bits == 0 { return (pairs.const_value[0], pairs.const_value[1]); }
+        let data: &[u8] = &self.data;
+        let main_index: usize = (index >> pairs.index_bits) as usize;
+        let mut literal: i64 = (index & ((1 << pairs.index_bits) - 1)) as i64 - (1 << (pairs.index_bits - 1));
+        let mut block: usize = read_u32(data, pairs.index_table + 6 * main_index) as usize;
+        literal += read_u16(data, pairs.index_table + 6 * main_index + 4) as i64;
+        let block_size = |block: usize| read_u16(data, pairs.size_table + 2 * block) as i64;
+        while literal < 0 {
+            block = block.wrapping_sub(1);
+            literal += block_size(block) + 1;
+        }
+        while literal > block_size(block) {
+            literal -= block_size(block) + 1;
+            block += 1;
+        }
+
+        let mut at: usize = pairs.data + (block << pairs.block_size);
+        let mut code: u64 = 0;
+        for i in 0..8 {
+            code = code << 8 | byte(data, at + i) as u64;
+        }
+        at += 8;
+        let mut bits: usize = 0;
+        let offset = |length: usize| read_u16(data, pairs.offsets + 2 * (length - pairs.min_length)) as usize;
+        let mut symbol: usize;
+        loop {
+            let mut length: usize = pairs.min_length;
+            while code < pairs.base[length - pairs.min_length] { length += 1; }
+            symbol = offset(length) + ((code - pairs.base[length - pairs.min_length]) >> (64 - length)) as usize;
+            let symbol_length: i64 = pairs.symbol_lengths[symbol] as i64;
+            if literal < symbol_length + 1 { break; }
+            literal -= symbol_length + 1;
+            code <<= length;
+            bits += length;
+            if bits >= 32 {
+                bits -= 32;
+                let next: u64 = u32::from_be_bytes([byte(data, at), byte(data, at + 1), byte(data, at + 2), byte(data, at + 3)]) as u64;
+                at += 4;
+                code |= next << bits;
+            }
+        }
+
+        // Symbols are pairs of smaller symbols, down to a single value
+        while pairs.symbol_lengths[symbol] != 0 {
+            let pattern: usize = pairs.symbol_patterns + 3 * symbol;
+            let left: usize = ((byte(data, pattern + 1) as usize & 0xF) << 8) | byte(data, pattern) as usize;
+            if literal < pairs.symbol_lengths[left] as i64 + 1 {
+                symbol = left;
+            } else {
+                literal -= pairs.symbol_lengths[left] as i64 + 1;
+                symbol = ((byte(data, pattern + 2) as usize) << 4) | (byte(data, pattern + 1) as usize >> 4);
+            }
+        }
+        let pattern: usize = pairs.symbol_patterns + 3 * symbol;
+        (byte(data, pattern), byte(data, pattern + 1))
+    }
+}
+
+impl PairsData {
+    // Reads the code description at the position and moves past it, also returns the flags
+    fn new(data: &[u8], at: &mut usize, positions: u64, kind: TableKind) -> Option<(PairsData, u8)> {
+        let start: usize = *at;
+        let flags: u8 = *data.get(start)?;
+        if flags & 0x80 != 0 {
+            // Every position has the same value
+            let value: u8 = if kind == TableKind::Wdl { *data.get(start + 1)? } else { 0 };
+            *at += 2;
+            return Some((PairsData { const_value: [value, 0], ..PairsData::default() }, flags));
+        }
+        let block_size: u32 = *data.get(start + 1)? as u32;
+        let index_bits: u32 = *data.get(start + 2)? as u32;
+        let real_blocks: u64 = read_u32(data, start + 4) as u64;
+        let blocks: u64 = real_blocks + *data.get(start + 3)? as u64;
+        let max_length: usize = *data.get(start + 8)? as usize;
+        let min_length: usize = *data.get(start + 9)? as usize;
+        if index_bits == 0 || index_bits > 63 || min_length == 0 || max_length < min_length || max_length > 64 { return None; }
+        let lengths: usize = max_length - min_length + 1;
+        let symbols: usize = read_u16(data, start + 10 + 2 * lengths) as usize;
+        let offsets: usize = start + 10;
+        let symbol_patterns: usize = start + 12 + 2 * lengths;
+        *at = symbol_patterns + 3 * symbols + (symbols & 1);
+        if *at > data.len() { return None; }
+
+        let index_count: u64 = (positions + (1 << index_bits) - 1) >> index_bits;
+
+        let mut symbol_lengths: Vec<u8> = vec![0; symbols];
+        let mut done: Vec<bool> = vec![false; symbols];
+        for symbol in 0..symbols {
+            symbol_length(data, symbol_patterns, symbol, &mut symbol_lengths, &mut done)?;
+        }
+
+        let offset = |i: usize| read_u16(data, offsets + 2 * i) as u64;
+        let mut base: Vec<u64> = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            base[i] = (base[i + 1].wrapping_add(offset(i)).wrapping_sub(offset(i + 1))) / 2;
+        }
+        for (i, value) in base.iter_mut().enumerate() {
+            *value = value.checked_shl((64 - (min_length + i)) as u32).unwrap_or(0);
+        }
+
+        let pairs: PairsData = PairsData {
+            index_bits,
+            block_size,
+            min_length,
+            const_value: [0; 2],
+            offsets,
+            symbol_patterns,
+            symbol_lengths,
+            base,
+            sizes: [6 * index_count, 2 * blocks, real_blocks << block_size],
+            index_table: 0,
+            size_table: 0,
+            data: 0,
+        };
+        Some((pairs, flags))
+    }
+}
+
+// A symbol stands for this many values more than one, a pattern of 0xFFF on the right means a single value
+fn symbol_length(data: &[u8], patterns: usize, symbol: usize, lengths: &mut [u8], done: &mut [bool]) -> Option<()> {
+    if done[symbol] { return Some(()); }
+    let pattern: usize = patterns + 3 * symbol;
+    let right: usize = ((byte(data, pattern + 2) as usize) << 4) | (byte(data, pattern + 1) as usize >> 4);
+    if right == 0xFFF {
+        lengths[symbol] = 0;
+    } else {
+        let left: usize = ((byte(data, pattern + 1) as usize & 0xF) << 8) | byte(data, pattern) as usize;
+        if left >= lengths.len() || right >= lengths.len() || left == symbol || right == symbol { return None; }
+        done[symbol] = true;
+        symbol_length(data, patterns, left, lengths, done)?;
+        symbol_length(data, patterns, right, lengths, done)?;
+        lengths[symbol] = lengths[left].wrapping_add(lengths[right]).wrapping_add(1);
+    }
+    done[symbol] = true;
+    Some(())
+}
+
+impl Encoding {
+    // The piece order and index factors of one table, from the header nibbles at the shift
+    fn new(header: &[u8], shift: u32, file: usize, files: &TableFiles) -> Encoding {
+        let more_pawns: bool = files.has_pawns && files.pawns[1] > 0;
+        let mut encoding: Encoding = Encoding::default();
+        for i in 0..files.pieces {
+            encoding.pieces[i] = (header[i + 1 + more_pawns as usize] >> shift) & 0xF;
+        }
+        let order: usize = ((header[0] >> shift) & 0xF) as usize;
+        let order2: usize = if more_pawns { ((header[1] >> shift) & 0xF) as usize } else { 0xF };
+
+        encoding.norm[0] = if files.has_pawns { files.pawns[0] } else if files.kk_encoding { 2 } else { 3 };
+        let mut k: usize = encoding.norm[0] as usize;
+        if more_pawns {
+            encoding.norm[k] = files.pawns[1];
+            k += encoding.norm[k] as usize;
+        }
+        let mut i: usize = k;
+        while i < files.pieces {
+            let mut j: usize = i;
+            while j < files.pieces && encoding.pieces[j] == encoding.pieces[i] {
+                encoding.norm[i] += 1;
+                j += 1;
+            }
+            i += encoding.norm[i] as usize;
+        }
+
+        let mut free: u64 = 64 - k as u64;
+        let mut f: u64 = 1;
+        let mut i: usize = 0;
+        while k < files.pieces || i == order || i == order2 {
+            if i == order {
+                encoding.factor[0] = f;
+                f *= if files.has_pawns {
+                    PAWN_INDICES.1[encoding.norm[0] as usize - 1][file]
+                } else if files.kk_encoding {
+                    462
+                } else {
+                    31332
+                };
+            } else if i == order2 {
+                let leading: usize = encoding.norm[0] as usize;
+                encoding.factor[leading] = f;
+                f *= subfactor(encoding.norm[leading] as u64, 48 - leading as u64);
+            } else {
+                encoding.factor[k] = f;
+                f *= subfactor(encoding.norm[k] as u64, free);
+                free -= encoding.norm[k] as u64;
+                k += encoding.norm[k] as usize;
+            }
+            i += 1;
+        }
+        encoding.positions = f;
+        encoding
+    }
+}
+
+// Puts the squares of the pieces of the next kind from the piece list at i, returns where the next kind starts
+fn fill_squares(board: &ChessBoard, pieces: &[u8; MAX_TABLE_PIECES], flip: bool, mirror: usize, squares: &mut [usize; MAX_TABLE_PIECES], mut i: usize) -> usize {
+    let code: u8 = pieces[i];
+    let white: bool = (code < 8) != flip;
+    let mut bits: u64 = piece_bits(board, white, code & 7);
+    if bits == 0 { return i + 1; }
+    while bits != 0 && i < MAX_TABLE_PIECES {
+        squares[i] = bits.trailing_zeros() as usize ^ mirror;
+        bits &= bits - 1;
+        i += 1;
+    }
+    i
+}
+
+// Moves the pawn that decides the table to the front and returns its file, counted from the nearest edge
+fn leading_pawn(squares: &mut [usize; MAX_TABLE_PIECES], files: &TableFiles) -> usize {
+    for i in 1..files.pawns[0] as usize {
+        if FLAP[squares[0]] > FLAP[squares[i]] { squares.swap(0, i); }
+    }
+    FILE_TO_FILE[squares[0] & 7]
+}
+
+fn flip_diagonal(square: usize) -> usize {
+    ((square & 7) << 3) | (square >> 3)
+}
+
+// Numbers the position within its table
+fn encode(squares: &mut [usize; MAX_TABLE_PIECES], encoding: &Encoding, files: &TableFiles) -> u64 {
+    let n: usize = files.pieces;
+    let squares: &mut [usize] = &mut squares[..n];
+    // Mirror so the first piece is on files a to d
+    if squares[0] & 4 != 0 {
+        for square in squares.iter_mut() { *square ^= 7; }
+    }
+
+    let mut index: u64;
+    let mut k: usize;
+    if !files.has_pawns {
+        // Without pawns also on ranks 1 to 4, and below the a1-h8 diagonal
+        if squares[0] & 0x20 != 0 {
+            for square in squares.iter_mut() { *square ^= 0x38; }
+        }
+        let unique: usize = if files.kk_encoding { 2 } else { 3 };
+        for i in 0..n {
+            if OFF_DIAG[squares[i]] != 0 {
+                if OFF_DIAG[squares[i]] > 0 && i < unique {
+                    for square in squares.iter_mut() { *square = flip_diagonal(*square); }
+                }
+                break;
+            }
+        }
+        if files.kk_encoding {
+            index = KK_IDX[TRIANGLE[squares[0]] as usize][squares[1]] as u64;
+            k = 2;
+        } else {
+            let (a, b, c): (usize, usize, usize) = (squares[0], squares[1], squares[2]);
+            let s1: usize = (b > a) as usize;
+            let s2: usize = (c > a) as usize + (c > b) as usize;
+            index = if OFF_DIAG[a] != 0 {
+                TRIANGLE[a] as usize * 63 * 62 + (b - s1) * 62 + (c - s2)
+            } else if OFF_DIAG[b] != 0 {
+                6 * 63 * 62 + DIAG[a] as usize * 28 * 62 + LOWER[b] as usize * 62 + c - s2
+            } else if OFF_DIAG[c] != 0 {
+                6 * 63 * 62 + 4 * 28 * 62 + DIAG[a] as usize * 7 * 28 + (DIAG[b] as usize - s1) * 28 + LOWER[c] as usize
+            } else {
+                6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + DIAG[a] as usize * 7 * 6 + (DIAG[b] as usize - s1) * 6 + (DIAG[c] as usize - s2)
+            } as u64;
+            k = 3;
+        }
+        index *= encoding.factor[0];
+    } else {
+        let leading: usize = files.pawns[0] as usize;
+        for i in 1..leading {
+            for j in i + 1..leading {
+                if PAWN_TWIST[squares[i]] < PAWN_TWIST[squares[j]] { squares.swap(i, j); }
+            }
+        }
+        k = leading;
+        index = PAWN_INDICES.0[k - 1][FLAP[squares[0]] as usize];
+        for i in 1..k {
+            index += BINOMIAL[k - i][PAWN_TWIST[squares[i]] as usize];
+        }
+        index *= encoding.factor[0];
+
+        // The other side's pawns can only stand on the 48 squares between the back ranks
+        if files.pawns[1] > 0 {
+            let t: usize = k + files.pawns[1] as usize;
+            squares[k..t].sort_unstable();
+            let mut s: u64 = 0;
+            for i in k..t {
+                let skips: usize = squares[..k].iter().filter(|square| squares[i] > **square).count();
+                s += BINOMIAL[i - k + 1][squares[i] - skips - 8];
+            }
+            index += s * encoding.factor[k];
+            k = t;
+        }
+    }
+
+    // The remaining pieces in groups of the same kind, each group as a combination of free squares
+    while k < n {
+        let t: usize = k + encoding.norm[k] as usize;
+        squares[k..t].sort_unstable();
+        let mut s: u64 = 0;
+        for i in k..t {
+            let skips: usize = squares[..k].iter().filter(|square| squares[i] > **square).count();
+            s += BINOMIAL[i - k + 1][squares[i] - skips];
+        }
+        index += s * encoding.factor[k];
+        k = t.max(k + 1);
+    }
+    index
+}
+
+fn is_checked(board: &ChessBoard) -> bool {
+    if board.white_turn { board.is_white_checked() } else { board.is_black_checked() }
+}
+
+fn is_mated(board: &ChessBoard) -> bool {
+    if board.white_turn { board.is_white_mated() } else { board.is_black_mated() }
+}
+
+fn is_pawn_move(board: &ChessBoard, m: &Move) -> bool {
+    board.get_square_piece(m.from) == Piece::Pawn
+}
+
+fn is_en_passant(board: &ChessBoard, m: &Move) -> bool {
+    is_pawn_move(board, m) && Some(m.to) == board.en_passant && board.get_square_piece(m.to) == Piece::None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, PositionBuilder, Square};
+    use crate::search::{search_with_tablebase, Score, SearchLimits};
+    use crate::timeman::StopFlag;
+    use crate::tt::TranspositionTable;
+
+    const KQVK_POSITIONS: usize = 31332;
+    // King, king and queen, the same for both sides to move
+    const KQVK_HEADER: [u8; 4] = [0x00, 0x66, 0xEE, 0x55];
+
+    fn board(fen: &str) -> ChessBoard {
+        ChessBoard::new_fen(fen).unwrap()
+    }
+
+    fn directory(name: &str, files: &[(&str, Vec<u8>)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("syzygy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        for (file, data) in files {
+            fs::write(path.join(file), data).unwrap();
+        }
+        path
+    }
+
+    fn pad(mut data: Vec<u8>, multiple: usize, rest: usize) -> Vec<u8> {
+        while data.len() % multiple != rest { data.push(0); }
+        data
+    }
+
+    // Every position has the same value, win is 4 and loss is 0
+    fn constant_wdl(white: u8, black: u8) -> Vec<u8> {
+        let mut data: Vec<u8> = WDL_MAGIC.to_le_bytes().to_vec();
+        data.push(1);
+        data.extend_from_slice(&KQVK_HEADER);
+        data.push(0);
+        data.extend_from_slice(&[0x80, white, 0x80, black]);
+        pad(data, 64, 16)
+    }
+
+    // Only white to move is stored, always one ply to the next capture or pawn move
+    fn constant_dtz() -> Vec<u8> {
+        let mut data: Vec<u8> = DTZ_MAGIC.to_le_bytes().to_vec();
+        data.push(0);
+        data.extend_from_slice(&KQVK_HEADER);
+        data.push(0);
+        data.extend_from_slice(&[0x80, 0]);
+        pad(data, 64, 16)
+    }
+
+    fn expected(index: usize) -> u8 {
+        if index.is_multiple_of(3) { 4 } else { 2 }
+    }
+
+    // White to move wins on every third index and draws otherwise, one bit per position.
+    // Blocks hold 3000 positions and the index table points at every 4096th, so lookups have to walk between blocks.
+    fn compressed_wdl() -> Vec<u8> {
+        const PER_BLOCK: usize = 3000;
+        let blocks: usize = KQVK_POSITIONS.div_ceil(PER_BLOCK);
+        let mut data: Vec<u8> = WDL_MAGIC.to_le_bytes().to_vec();
+        data.push(1);
+        data.extend_from_slice(&KQVK_HEADER);
+        data.push(0);
+        // 512 byte blocks, 4096 positions per index entry, both symbols one bit long
+        data.extend_from_slice(&[0, 9, 12, 0]);
+        data.extend_from_slice(&(blocks as u32).to_le_bytes());
+        data.extend_from_slice(&[1, 1, 0, 0, 2, 0]);
+        data.extend_from_slice(&[4, 0xF0, 0xFF, 2, 0xF0, 0xFF]);
+        data.extend_from_slice(&[0x80, 0]);
+        for i in 0..KQVK_POSITIONS.div_ceil(4096) {
+            let middle: usize = i * 4096 + 2048;
+            data.extend_from_slice(&((middle / PER_BLOCK) as u32).to_le_bytes());
+            data.extend_from_slice(&((middle % PER_BLOCK) as u16).to_le_bytes());
+        }
+        for block in 0..blocks {
+            let count: usize = PER_BLOCK.min(KQVK_POSITIONS - block * PER_BLOCK);
+            data.extend_from_slice(&((count - 1) as u16).to_le_bytes());
+        }
+        data = pad(data, 64, 0);
+        for block in 0..blocks {
+            let mut bits: Vec<u8> = vec![0; 512];
+            for i in 0..PER_BLOCK.min(KQVK_POSITIONS - block * PER_BLOCK) {
+                if expected(block * PER_BLOCK + i) == 2 { bits[i / 8] |= 0x80 >> (i % 8); }
+            }
+            data.extend_from_slice(&bits);
+        }
+        pad(data, 64, 16)
+    }
+
+    fn constant_tables(name: &str) -> Tablebase {
+        Tablebase::open(directory(name, &[("KQvK.rtbw", constant_wdl(4, 0)), ("KQvK.rtbz", constant_dtz())])).unwrap()
+    }
+
+    #[test]
+    fn material() {
+        let material = parse_material("KRPvKR").unwrap();
+        assert_eq!((material[1], material[4], material[6], material[12], material[14]), (1, 1, 1, 1, 1));
+        assert_eq!(material_key(&board_material(&board("4k3/8/8/8/8/8/P7/R2rK3 w - - 0 1")), false), material_key(&material, false));
+        assert_eq!(material_key(&board_material(&board("4k3/p7/8/8/8/8/8/R2rK3 w - - 0 1")), false), material_key(&material, true));
+        assert_eq!(parse_material("KRvKR").map(|m| material_key(&m, false) == material_key(&m, true)), Some(true));
+        assert_eq!(parse_material("KRvR"), None);
+        assert_eq!(parse_material("KXvK"), None);
+        assert_eq!(parse_material("KQQQQvKQQ"), None);
+        assert!(Wdl::Loss < Wdl::BlessedLoss && Wdl::Draw < Wdl::CursedWin && Wdl::CursedWin < Wdl::Win);
+        assert_eq!(Wdl::from_dtz(0, 30), Wdl::Win);
+        assert_eq!(Wdl::from_dtz(80, 30), Wdl::CursedWin);
+        assert_eq!(Wdl::from_dtz(80, -30), Wdl::BlessedLoss);
+    }
+
+    #[test]
+    fn open_directory() {
+        assert!(matches!(Tablebase::open("does/not/exist"), Err(SyzygyError::Io(_))));
+
+        let tablebase = Tablebase::open(directory("empty", &[("notes.txt", vec![1, 2, 3])])).unwrap();
+        assert!(tablebase.is_empty());
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/K6Q w - - 0 1")), None);
+
+        let tablebase = constant_tables("constant");
+        assert_eq!((tablebase.len(), tablebase.max_pieces()), (1, 3));
+        // More pieces than the tables hold, or castling rights
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/K5RQ w - - 0 1")), None);
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/4K2R w K - 0 1")), None);
+
+        // A file of the wrong size is ignored
+        let mut broken: Vec<u8> = constant_wdl(4, 0);
+        broken.push(0);
+        let tablebase = Tablebase::open(directory("broken", &[("KQvK.rtbw", broken)])).unwrap();
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/K6Q w - - 0 1")), None);
+    }
+
+    #[test]
+    fn probe_wdl() {
+        let tablebase = constant_tables("wdl");
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/K6Q w - - 0 1")), Some(Wdl::Win));
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/8/8/8/8/8/K6Q b - - 0 1")), Some(Wdl::Loss));
+        // The same table with the colors swapped
+        assert_eq!(tablebase.probe_wdl(&board("k6q/8/8/8/8/8/8/K7 b - - 0 1")), Some(Wdl::Win));
+        assert_eq!(tablebase.probe_wdl(&board("k6q/8/8/8/8/8/8/K7 w - - 0 1")), Some(Wdl::Loss));
+        // Taking the queen leaves two kings
+        assert_eq!(tablebase.probe_wdl(&board("8/8/8/8/8/8/1Q6/k6K b - - 0 1")), Some(Wdl::Draw));
+        assert_eq!(tablebase.probe_wdl(&board("8/8/8/8/8/8/8/k1K5 w - - 0 1")), Some(Wdl::Draw));
+    }
+
+    #[test]
+    fn probe_dtz_and_best_move() {
+        let tablebase = constant_tables("dtz");
+        assert_eq!(tablebase.probe_dtz(&board("k7/8/2K5/8/8/8/8/7Q w - - 0 1")), Some(1));
+        // Black to move is not stored, so it is looked up through the white positions after every move
+        assert_eq!(tablebase.probe_dtz(&board("8/8/8/3k4/8/8/8/K6Q b - - 0 1")), Some(-2));
+        assert_eq!(tablebase.probe_dtz(&board("8/8/8/8/8/8/1Q6/k6K b - - 0 1")), Some(0));
+
+        let b = board("k7/8/1K6/8/8/8/8/7Q w - - 0 1");
+        let best = tablebase.best_move(&b).unwrap();
+        assert_eq!((best.wdl, best.dtz), (Wdl::Win, 1));
+        let mut child = b;
+        child.make_move(best.m, false);
+        assert!(child.is_black_mated());
+
+        // Next to the black king the queen is lost
+        let b = board("K7/8/8/8/8/8/1k6/7Q w - - 0 1");
+        let moves = tablebase.root_moves(&b).unwrap();
+        assert_eq!(moves.len(), b.generate_moves().len());
+        let qc1 = moves.iter().find(|r| r.m.to_uci() == "h1c1").unwrap();
+        assert_eq!((qc1.wdl, qc1.dtz), (Wdl::Draw, 0));
+        assert_eq!(tablebase.best_move(&b).unwrap().wdl, Wdl::Win);
+        // Late in the fifty moves the same win is cursed
+        let b = board("K7/8/8/8/8/8/1k6/7Q w - - 99 80");
+        assert!(tablebase.root_moves(&b).unwrap().iter().all(|r| r.wdl != Wdl::Win));
+    }
+
+    #[test]
+    fn compressed_table() {
+        let tablebase = Tablebase::open(directory("compressed", &[("KQvK.rtbw", compressed_wdl())])).unwrap();
+        let files: &TableFiles = &tablebase.files[0];
+        let table: &Table = files.table(TableKind::Wdl).unwrap();
+        let encoding: &Encoding = &table.encodings[0][0];
+        assert_eq!(encoding.positions, KQVK_POSITIONS as u64);
+        for index in 0..KQVK_POSITIONS {
+            assert_eq!(table.decompress(&encoding.pairs, index as u64).0, expected(index));
+        }
+
+        // All legal placements get an index in range, the same for every mirror image of the board
+        let mirrors: [fn(usize) -> usize; 3] = [|s| s ^ 7, |s| s ^ 0x38, flip_diagonal];
+        for white_king in 0..64usize {
+            for black_king in (0..64usize).step_by(3) {
+                let (dx, dy) = ((white_king % 8).abs_diff(black_king % 8), (white_king / 8).abs_diff(black_king / 8));
+                if dx <= 1 && dy <= 1 { continue; }
+                for queen in (0..64).filter(|q| *q != white_king && *q != black_king) {
+                    let mut squares: [usize; MAX_TABLE_PIECES] = [white_king, black_king, queen, 0, 0, 0, 0];
+                    let index: u64 = encode(&mut squares.clone(), encoding, files);
+                    assert!(index < KQVK_POSITIONS as u64);
+                    for mirror in mirrors.iter() {
+                        for square in squares.iter_mut().take(3) { *square = mirror(*square); }
+                        assert_eq!(encode(&mut squares.clone(), encoding, files), index);
+                    }
+                }
+            }
+        }
+
+        let b = board("8/8/3k4/8/8/8/5Q2/1K6 w - - 0 1");
+        let mut flipped = board("8/8/4k3/8/8/8/2Q5/6K1 w - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&b), tablebase.probe_wdl(&flipped));
+        flipped.white_turn = false;
+        assert_eq!(tablebase.probe_wdl(&flipped), Some(Wdl::Loss));
+    }
+
+    #[test]
+    fn search_uses_tables() {
+        let tablebase = constant_tables("search");
+        let mut tt = TranspositionTable::new(1);
+        // Qxa1 reaches a won table position
+        let b = board("4k3/8/8/8/8/8/8/r3QK2 w - - 0 1");
+        let result = search_with_tablebase(&b, SearchLimits::depth(3), &mut tt, &StopFlag::new(), Some(&tablebase), &mut |_| ());
+        assert_eq!(result.best_move.map(|m| m.to_uci()), Some("e1a1".to_string()));
+        assert_eq!(result.score, Score::Tablebase(1));
+        assert!(result.tbhits > 0);
+
+        // At the root only moves that keep the win are searched
+        let b = board("K7/8/8/8/8/8/1k6/7Q w - - 0 1");
+        let result = search_with_tablebase(&b, SearchLimits::depth(1), &mut tt, &StopFlag::new(), Some(&tablebase), &mut |_| ());
+        let kept = tablebase.root_moves(&b).unwrap().into_iter().find(|r| Some(r.m) == result.best_move).unwrap();
+        assert_eq!(kept.wdl, Wdl::Win);
+    }
+
+    // Checks every KQvK and KRvK position against real tables when SYZYGY_PATH points at them, skipped otherwise
+    #[test]
+    fn real_tables() {
+        let path: String = match std::env::var("SYZYGY_PATH") {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let tablebase = Tablebase::open(&path).unwrap();
+        let mut checked: usize = 0;
+        for piece in [Piece::Queen, Piece::Rook] {
+            for white_king in 0..64 {
+                for white_piece in 0..64 {
+                    for black_king in 0..64 {
+                        if white_piece == white_king || black_king == white_king || black_king == white_piece { continue; }
+                        let mut builder = PositionBuilder::new();
+                        builder.put(Square::new(white_king / 8, white_king % 8), Piece::King, Color::White)
+                            .put(Square::new(white_piece / 8, white_piece % 8), piece, Color::White)
+                            .put(Square::new(black_king / 8, black_king % 8), Piece::King, Color::Black);
+                        let white: ChessBoard = match builder.build() {
+                            Ok(b) => b,
+                            Err(_) => continue,
+                        };
+                        if tablebase.probe_wdl(&white).is_none() { continue; }
+                        checked += 1;
+                        assert_eq!(tablebase.probe_wdl(&white), Some(Wdl::Win), "{}", white.to_fen());
+                        if white.generate_moves().iter().any(|m| {
+                            let mut after: ChessBoard = white;
+                            after.make_move(*m, false);
+                            after.is_black_mated()
+                        }) {
+                            assert_eq!(tablebase.probe_dtz(&white), Some(1), "{}", white.to_fen());
+                        }
+                        assert_eq!(tablebase.best_move(&white).map(|r| r.wdl), Some(Wdl::Win), "{}", white.to_fen());
+
+                        let black: ChessBoard = match builder.side_to_move(Color::Black).build() {
+                            Ok(b) => b,
+                            Err(_) => continue,
+                        };
+                        let moves: Vec<Move> = black.generate_moves();
+                        let expected: Wdl = if black.is_black_mated() {
+                            Wdl::Loss
+                        } else if moves.is_empty() || moves.iter().any(|m| black.is_capture(m)) {
+                            Wdl::Draw
+                        } else {
+                            Wdl::Loss
+                        };
+                        assert_eq!(tablebase.probe_wdl(&black), Some(expected), "{}", black.to_fen());
+                    }
+                }
+            }
+        }
+        assert!(checked > 0, "no KQvK or KRvK tables in {}", path);
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::search::SearchLimits;
+
+// Kept back from the clock for communication with the GUI
+pub const MOVE_OVERHEAD_MS: u64 = 30;
+// Moves the remaining time is spread over when the time control does not say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+// Shared between the searching thread and whoever wants to stop it, clones all point to the same flag
+#[derive(Clone, Debug, Default)]
+pub struct StopFlag {
+    flag: Arc<AtomicBool>,
+}
+
+impl StopFlag {
+    pub fn new() -> StopFlag {
+        StopFlag::default()
+    }
+
+    pub fn stop(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    // No new iteration is started after the soft limit, the hard limit aborts the running one
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, white_turn: bool) -> TimeManager {
+        let (soft, hard): (Option<u64>, Option<u64>) = allocate(limits, white_turn);
+        TimeManager {
+            start: Instant::now(),
+            soft: soft.map(Duration::from_millis),
+            hard: hard.map(Duration::from_millis),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        self.soft.is_some_and(|soft| self.elapsed() >= soft)
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+}
+
+// Soft and hard limits in milliseconds, None when the search may take as long as it likes
+fn allocate(limits: &SearchLimits, white_turn: bool) -> (Option<u64>, Option<u64>) {
+    if limits.infinite { return (None, None); }
+    if let Some(movetime) = limits.movetime {
+        return (Some(movetime), Some(movetime));
+    }
+    let (time, increment): (Option<u64>, u64) = if white_turn {
+        (limits.wtime, limits.winc.unwrap_or(0))
+    } else {
+        (limits.btime, limits.binc.unwrap_or(0))
+    };
+    let time: u64 = match time {
+        Some(time) => time,
+        None => return (None, None),
+    };
+
+    let available: u64 = time.saturating_sub(MOVE_OVERHEAD_MS).max(1);
+    let moves_to_go: u64 = limits.movestogo.map(|n| n.max(1) as u64).unwrap_or(DEFAULT_MOVES_TO_GO);
+    let soft: u64 = (available / moves_to_go + increment * 3 / 4).min(available);
+    // With the last move before the time control there is nothing to save time for
+    let hard: u64 = if moves_to_go == 1 { available } else { (soft * 4).min(available / 2).max(soft) };
+    (Some(soft.max(1)), Some(hard.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(soft: u64, hard: u64) -> (Option<Duration>, Option<Duration>) {
+        (Some(Duration::from_millis(soft)), Some(Duration::from_millis(hard)))
+    }
+
+    #[test]
+    fn allocation() {
+        let tm = |l: SearchLimits, white: bool| {
+            let tm = TimeManager::new(&l, white);
+            (tm.soft_limit(), tm.hard_limit())
+        };
+        assert_eq!(tm(SearchLimits::depth(5), true), (None, None));
+        assert_eq!(tm(SearchLimits::infinite(), true), (None, None));
+        assert_eq!(tm(SearchLimits::movetime(500), false), limits(500, 500));
+
+        // 60s + 1s increment, the other side's clock does not matter
+        let clock = SearchLimits::clock(60030, 1000, 1000, 0);
+        assert_eq!(tm(clock, true), limits(2750, 11000));
+        assert_eq!(tm(clock, false), limits(32, 128));
+
+        let mut l = SearchLimits::clock(10030, 10030, 0, 0);
+        l.movestogo = Some(5);
+        assert_eq!(tm(l, true), limits(2000, 5000));
+        l.movestogo = Some(1);
+        assert_eq!(tm(l, true), limits(10000, 10000));
+
+        // Never more than what is on the clock, even with a large increment
+        let short = SearchLimits::clock(530, 530, 2000, 2000);
+        let (soft, hard) = tm(short, true);
+        assert!(soft.unwrap() <= Duration::from_millis(500) && hard.unwrap() <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn stop_flag_is_shared() {
+        let flag = StopFlag::new();
+        let clone = flag.clone();
+        assert!(!clone.is_stopped());
+        std::thread::spawn(move || flag.stop()).join().unwrap();
+        assert!(clone.is_stopped());
+        clone.reset();
+        assert!(!clone.is_stopped());
+    }
+}
@@ -0,0 +1,788 @@
+use std::time::Duration;
+
+use crate::pgn::{parse_tag, strip_move_number, wrap, write_tags, PgnError, PgnGame};
+use crate::search::{Score, TB_WIN_CENTIPAWNS};
+use crate::{ChessBoard, Move};
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+// The NAGs that have a symbol of their own, as written after moves or in print
+const NAG_SYMBOLS: [(u8, &str); 14] = [
+    (1, "!"), (2, "?"), (3, "!!"), (4, "??"), (5, "!?"), (6, "?!"), (10, "="),
+    (13, "∞"), (14, "⩲"), (15, "⩱"), (16, "±"), (17, "∓"), (18, "+-"), (19, "-+")];
+
+pub fn nag_symbol(nag: u8) -> Option<&'static str> {
+    NAG_SYMBOLS.iter().find(|(n, _)| *n == nag).map(|(_, symbol)| *symbol)
+}
+
+// Reads $n as well as the symbols
+pub fn parse_nag(value: &str) -> Option<u8> {
+    match value.strip_prefix('$') {
+        Some(number) => number.parse::<u8>().ok(),
+        None => NAG_SYMBOLS.iter().find(|(_, symbol)| *symbol == value).map(|(n, _)| *n),
+    }
+}
+
+// A node of a GameTree, only valid for the tree it came from
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+#[derive(Clone)]
+struct Node {
+    parent: Option<usize>,
+    m: Option<Move>,
+    san: String,
+    // The position after the move
+    board: ChessBoard,
+    // The main continuation first, then the variations
+    children: Vec<usize>,
+    // Written before the move, only used at the start of a variation
+    starting_comment: String,
+    comment: String,
+    nags: Vec<u8>,
+    clock: Option<Duration>,
+    eval: Option<Score>,
+}
+
+impl Node {
+    fn new(parent: Option<usize>, m: Option<Move>, san: String, board: ChessBoard) -> Node {
+        Node {
+            parent, m, san, board,
+            children: Vec::new(),
+            starting_comment: String::new(),
+            comment: String::new(),
+            nags: Vec::new(),
+            clock: None,
+            eval: None,
+        }
+    }
+}
+
+// A game with its variations. Every node is a move, except the root which is the start position. The first child of
+// a node is the main continuation, the others are variations of it. Deleted nodes are only unlinked from the tree
+#[derive(Clone)]
+pub struct GameTree {
+    pub tags: Vec<(String, String)>,
+    pub result: String,
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+impl Default for GameTree {
+    fn default() -> GameTree {
+        GameTree::new()
+    }
+}
+
+impl GameTree {
+    pub fn new() -> GameTree {
+        GameTree::from_position(&ChessBoard::new())
+    }
+
+    // Starts with the tags of an empty PgnGame, including FEN when the position is not the standard one
+    pub fn from_position(start: &ChessBoard) -> GameTree {
+        GameTree {
+            tags: PgnGame::from_moves(start, &[]).tags,
+            result: "*".to_string(),
+            nodes: vec![Node::new(None, None, String::new(), *start)],
+            current: 0,
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn current(&self) -> NodeId {
+        NodeId(self.current)
+    }
+
+    // The position at the current node
+    pub fn board(&self) -> &ChessBoard {
+        &self.nodes[self.current].board
+    }
+
+    pub fn node_board(&self, node: NodeId) -> &ChessBoard {
+        &self.nodes[node.0].board
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent.map(NodeId)
+    }
+
+    // The main continuation first, then its variations
+    pub fn children(&self, node: NodeId) -> Vec<NodeId> {
+        self.nodes[node.0].children.iter().map(|i| NodeId(*i)).collect()
+    }
+
+    // None for the root
+    pub fn node_move(&self, node: NodeId) -> Option<Move> {
+        self.nodes[node.0].m
+    }
+
+    pub fn san(&self, node: NodeId) -> &str {
+        &self.nodes[node.0].san
+    }
+
+    // The number of moves from the root
+    pub fn ply(&self, node: NodeId) -> usize {
+        let mut ply: usize = 0;
+        let mut index: usize = node.0;
+        while let Some(parent) = self.nodes[index].parent {
+            ply += 1;
+            index = parent;
+        }
+        ply
+    }
+
+    // False for deleted nodes
+    pub fn is_mainline(&self, node: NodeId) -> bool {
+        let mut index: usize = node.0;
+        while let Some(parent) = self.nodes[index].parent {
+            if self.nodes[parent].children.first() != Some(&index) { return false; }
+            index = parent;
+        }
+        true
+    }
+
+    // Whether the node is still in the tree, a node is gone when it or one of the nodes before it was deleted
+    pub fn contains(&self, node: NodeId) -> bool {
+        let mut index: usize = node.0;
+        while let Some(parent) = self.nodes[index].parent {
+            if !self.nodes[parent].children.contains(&index) { return false; }
+            index = parent;
+        }
+        true
+    }
+
+    // The nodes of the main line after the root
+    pub fn mainline(&self) -> Vec<NodeId> {
+        let mut nodes: Vec<NodeId> = Vec::new();
+        let mut index: usize = 0;
+        while let Some(&child) = self.nodes[index].children.first() {
+            nodes.push(NodeId(child));
+            index = child;
+        }
+        nodes
+    }
+
+    pub fn mainline_moves(&self) -> Vec<Move> {
+        self.mainline().into_iter().filter_map(|node| self.node_move(node)).collect()
+    }
+
+    // Goes to the node of the move after the current one, adding it as the main continuation or else as a variation.
+    // None if the move is not legal
+    pub fn play(&mut self, m: Move) -> Option<NodeId> {
+        let board: ChessBoard = self.nodes[self.current].board;
+        if !board.generate_moves().contains(&m) { return None; }
+        let child: usize = self.add_child(self.current, m);
+        self.current = child;
+        Some(NodeId(child))
+    }
+
+    // Accepts SAN and UCI
+    pub fn play_str(&mut self, value: &str) -> Option<NodeId> {
+        let board: &ChessBoard = self.board();
+        let m: Move = board.parse_san(value).or_else(|| board.parse_uci_move(value))?;
+        self.play(m)
+    }
+
+    fn add_child(&mut self, parent: usize, m: Move) -> usize {
+        if let Some(&child) = self.nodes[parent].children.iter().find(|&&child| self.nodes[child].m == Some(m)) {
+            return child;
+        }
+        let mut board: ChessBoard = self.nodes[parent].board;
+        let san: String = board.to_san(&m);
+        board.make_move(m, false);
+        self.nodes.push(Node::new(Some(parent), Some(m), san, board));
+        let child: usize = self.nodes.len() - 1;
+        self.nodes[parent].children.push(child);
+        child
+    }
+
+    pub fn go_to(&mut self, node: NodeId) {
+        self.current = node.0;
+    }
+
+    pub fn go_to_start(&mut self) {
+        self.current = 0;
+    }
+
+    // Follows the main continuation from the current node to its end
+    pub fn go_to_end(&mut self) {
+        while self.go_next() {}
+    }
+
+    // Goes to the main continuation, false at the end of a line
+    pub fn go_next(&mut self) -> bool {
+        self.enter_variation(0)
+    }
+
+    pub fn go_prev(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Goes to one of the moves after the current one, 0 is the main continuation and 1 the first variation
+    pub fn enter_variation(&mut self, index: usize) -> bool {
+        match self.nodes[self.current].children.get(index) {
+            Some(&child) => {
+                self.current = child;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Moves the variation one place up among its siblings, false if it already was the main continuation or was deleted
+    pub fn promote_variation(&mut self, node: NodeId) -> bool {
+        let Some(parent) = self.nodes[node.0].parent else { return false; };
+        let children: &mut Vec<usize> = &mut self.nodes[parent].children;
+        let Some(position) = children.iter().position(|&child| child == node.0) else { return false; };
+        if position == 0 { return false; }
+        children.swap(position, position - 1);
+        true
+    }
+
+    // Makes the line leading to the node the main line, deleted nodes are left out of the tree
+    pub fn promote_to_mainline(&mut self, node: NodeId) {
+        if !self.contains(node) { return; }
+        let mut index: usize = node.0;
+        while let Some(parent) = self.nodes[index].parent {
+            let children: &mut Vec<usize> = &mut self.nodes[parent].children;
+            children.retain(|&child| child != index);
+            children.insert(0, index);
+            index = parent;
+        }
+    }
+
+    // Removes the node and everything after it. The root and nodes that are already deleted can not be deleted.
+    // When the current node is removed the parent becomes the current node
+    pub fn delete_variation(&mut self, node: NodeId) -> bool {
+        let Some(parent) = self.nodes[node.0].parent else { return false; };
+        if !self.contains(node) { return false; }
+        let mut index: usize = self.current;
+        loop {
+            if index == node.0 {
+                self.current = parent;
+                break;
+            }
+            match self.nodes[index].parent {
+                Some(p) => index = p,
+                None => break,
+            }
+        }
+        self.nodes[parent].children.retain(|&child| child != node.0);
+        true
+    }
+
+    pub fn comment(&self, node: NodeId) -> &str {
+        &self.nodes[node.0].comment
+    }
+
+    // The comment after the move, or before the first move for the root
+    pub fn set_comment(&mut self, node: NodeId, comment: &str) {
+        self.nodes[node.0].comment = comment.to_string();
+    }
+
+    pub fn starting_comment(&self, node: NodeId) -> &str {
+        &self.nodes[node.0].starting_comment
+    }
+
+    // The comment before the move, written when the move starts a variation
+    pub fn set_starting_comment(&mut self, node: NodeId, comment: &str) {
+        self.nodes[node.0].starting_comment = comment.to_string();
+    }
+
+    pub fn nags(&self, node: NodeId) -> &[u8] {
+        &self.nodes[node.0].nags
+    }
+
+    pub fn add_nag(&mut self, node: NodeId, nag: u8) {
+        if !self.nodes[node.0].nags.contains(&nag) {
+            self.nodes[node.0].nags.push(nag);
+        }
+    }
+
+    pub fn remove_nag(&mut self, node: NodeId, nag: u8) {
+        self.nodes[node.0].nags.retain(|&n| n != nag);
+    }
+
+    // The time left on the clock of the player after the move, written as [%clk 1:30:00]
+    pub fn clock(&self, node: NodeId) -> Option<Duration> {
+        self.nodes[node.0].clock
+    }
+
+    pub fn set_clock(&mut self, node: NodeId, clock: Option<Duration>) {
+        self.nodes[node.0].clock = clock;
+    }
+
+    // The evaluation after the move from white's point of view, written as [%eval 0.31] or [%eval #-3]
+    pub fn eval(&self, node: NodeId) -> Option<Score> {
+        self.nodes[node.0].eval
+    }
+
+    pub fn set_eval(&mut self, node: NodeId, eval: Option<Score>) {
+        self.nodes[node.0].eval = eval;
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Only the main line
+    pub fn to_pgn_game(&self) -> PgnGame {
+        PgnGame {
+            tags: self.tags.clone(),
+            moves: self.mainline().into_iter().map(|node| self.san(node).to_string()).collect(),
+            result: self.result.clone(),
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn: String = write_tags(&self.tags, &self.result);
+        let mut tokens: Vec<String> = Vec::new();
+        if let Some(comment) = self.comment_text(0) {
+            tokens.push(comment);
+        }
+        self.write_children(0, &mut tokens, true);
+        tokens.push(self.result.clone());
+        pgn.push_str(&wrap(&tokens));
+        pgn.push('\n');
+        pgn
+    }
+
+    // The main continuation, then its variations in parentheses, then the rest of the main line
+    fn write_children(&self, parent: usize, tokens: &mut Vec<String>, number: bool) {
+        let Some(&main) = self.nodes[parent].children.first() else { return; };
+        let mut number: bool = self.write_move(main, tokens, number);
+        for &variation in &self.nodes[parent].children[1..] {
+            let start: usize = tokens.len();
+            let after: bool = self.write_move(variation, tokens, true);
+            self.write_children(variation, tokens, after);
+            tokens[start].insert(0, '(');
+            tokens.last_mut().unwrap().push(')');
+            number = true;
+        }
+        self.write_children(main, tokens, number);
+    }
+
+    // Returns whether the next black move needs its number again
+    fn write_move(&self, index: usize, tokens: &mut Vec<String>, mut number: bool) -> bool {
+        let node: &Node = &self.nodes[index];
+        let before: &ChessBoard = &self.nodes[node.parent.unwrap()].board;
+        if !node.starting_comment.is_empty() {
+            tokens.push(format!("{{{}}}", node.starting_comment));
+            number = true;
+        }
+        tokens.push(if before.white_turn {
+            format!("{}. {}", before.fullmove_number, node.san)
+        } else if number {
+            format!("{}... {}", before.fullmove_number, node.san)
+        } else {
+            node.san.clone()
+        });
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+        match self.comment_text(index) {
+            Some(comment) => {
+                tokens.push(comment);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn comment_text(&self, index: usize) -> Option<String> {
+        let node: &Node = &self.nodes[index];
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(clock) = node.clock {
+            parts.push(format!("[%clk {}]", format_clock(clock)));
+        }
+        if let Some(eval) = node.eval {
+            parts.push(match eval {
+                Score::Centipawns(cp) => format!("[%eval {:.2}]", cp as f64 / 100.0),
+                Score::Mate(moves) => format!("[%eval #{}]", moves),
+                Score::Tablebase(plies) => format!("[%eval {:.2}]", (plies.signum() * (TB_WIN_CENTIPAWNS - plies.abs())) as f64 / 100.0),
+            });
+        }
+        if !node.comment.is_empty() {
+            parts.push(node.comment.clone());
+        }
+        if parts.is_empty() { None } else { Some(format!("{{{}}}", parts.join(" "))) }
+    }
+
+    // Sets the comment and takes the clock and eval commands out of it
+    fn add_comment(&mut self, index: usize, text: &str) {
+        let mut parts: Vec<&str> = Vec::new();
+        let mut rest: &str = text;
+        while let Some(start) = rest.find("[%") {
+            let Some(length) = rest[start..].find(']') else { break; };
+            let command: &str = &rest[start + 2..start + length];
+            let (name, value): (&str, &str) = command.split_once(' ').unwrap_or((command, ""));
+            let used: bool = match name {
+                "clk" => parse_clock(value.trim()).map(|clock| self.nodes[index].clock = Some(clock)).is_some(),
+                "eval" => parse_eval(value.trim()).map(|eval| self.nodes[index].eval = Some(eval)).is_some(),
+                _ => false,
+            };
+            if used {
+                parts.push(&rest[..start]);
+            } else {
+                parts.push(&rest[..start + length + 1]);
+            }
+            rest = &rest[start + length + 1..];
+        }
+        parts.push(rest);
+        let text: String = parts.iter().map(|part| part.trim()).filter(|part| !part.is_empty()).collect::<Vec<&str>>().join(" ");
+        let comment: &mut String = &mut self.nodes[index].comment;
+        if !text.is_empty() {
+            if !comment.is_empty() { comment.push(' '); }
+            comment.push_str(&text);
+        }
+    }
+}
+
+fn format_clock(clock: Duration) -> String {
+    let seconds: u64 = clock.as_secs();
+    let mut text: String = format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if clock.subsec_millis() > 0 {
+        text.push_str(format!(".{:03}", clock.subsec_millis()).trim_end_matches('0'));
+    }
+    text
+}
+
+fn parse_clock(value: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() > 3 { return None; }
+    let (last, rest): (&&str, &[&str]) = parts.split_last()?;
+    for part in rest {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    let (whole, fraction): (&str, &str) = last.split_once('.').unwrap_or((last, ""));
+    seconds = seconds * 60 + whole.parse::<u64>().ok()?;
+    let mut millis: u64 = 0;
+    for (i, digit) in fraction.chars().take(3).enumerate() {
+        millis += digit.to_digit(10)? as u64 * [100, 10, 1][i];
+    }
+    Some(Duration::from_millis(seconds * 1000 + millis))
+}
+
+fn parse_eval(value: &str) -> Option<Score> {
+    match value.strip_prefix('#') {
+        Some(moves) => moves.parse::<i32>().ok().map(Score::Mate),
+        None => value.parse::<f64>().ok().filter(|pawns| pawns.is_finite()).map(|pawns| Score::Centipawns((pawns * 100.0).round() as i32)),
+    }
+}
+
+// Reads every game in the text with its variations, comments and NAGs
+pub fn parse_pgn_tree(text: &str) -> Result<Vec<GameTree>, PgnError> {
+    let mut trees: Vec<GameTree> = Vec::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut tree: Option<GameTree> = None;
+    // The nodes to go back to when a variation ends
+    let mut stack: Vec<usize> = Vec::new();
+    // Right after the start of the game or a variation, where a comment belongs to the next move
+    let mut at_start: bool = true;
+    let mut starting_comment: String = String::new();
+    let mut chars = text.chars().peekable();
+
+    // The tree is made once the tags are known
+    fn tree_for<'a>(tree: &'a mut Option<GameTree>, tags: &[(String, String)]) -> Result<&'a mut GameTree, PgnError> {
+        if tree.is_none() {
+            let game: PgnGame = PgnGame { tags: tags.to_vec(), moves: Vec::new(), result: "*".to_string() };
+            let mut new: GameTree = GameTree::from_position(&game.start_position()?);
+            new.tags = tags.to_vec();
+            new.result = game.tag("Result").unwrap_or("*").to_string();
+            *tree = Some(new);
+        }
+        Ok(tree.as_mut().unwrap())
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if stack.is_empty() => {
+                // Tags after movetext start the next game
+                if let Some(done) = tree.take() {
+                    trees.push(done);
+                    tags.clear();
+                    at_start = true;
+                }
+                let mut tag: String = String::new();
+                let mut in_string: bool = false;
+                loop {
+                    match chars.next() {
+                        Some('\\') if in_string => if let Some(escaped) = chars.next() { tag.push(escaped) },
+                        Some('"') => { in_string = !in_string; tag.push('"'); },
+                        Some(']') if !in_string => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(PgnError::InvalidTag(tag)),
+                    }
+                }
+                let (name, value) = parse_tag(&tag).ok_or(PgnError::InvalidTag(tag.clone()))?;
+                match tags.iter_mut().find(|(n, _)| *n == name) {
+                    Some(existing) => existing.1 = value,
+                    None => tags.push((name, value)),
+                }
+            },
+            '{' => {
+                let mut comment: String = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                let game: &mut GameTree = tree_for(&mut tree, &tags)?;
+                if at_start && !stack.is_empty() {
+                    if !starting_comment.is_empty() { starting_comment.push(' '); }
+                    starting_comment.push_str(comment.trim());
+                } else {
+                    game.add_comment(game.current, &comment);
+                }
+            },
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' { break; }
+                }
+            },
+            '(' => {
+                let game: &mut GameTree = tree_for(&mut tree, &tags)?;
+                let Some(parent) = game.nodes[game.current].parent else { return Err(PgnError::UnterminatedVariation); };
+                stack.push(game.current);
+                game.current = parent;
+                at_start = true;
+                starting_comment.clear();
+            },
+            ')' => {
+                let game: &mut GameTree = tree_for(&mut tree, &tags)?;
+                game.current = stack.pop().ok_or(PgnError::UnterminatedVariation)?;
+                at_start = false;
+            },
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token: String = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "{}();[".contains(*next) { break; }
+                    token.push(chars.next().unwrap());
+                }
+                let game: &mut GameTree = tree_for(&mut tree, &tags)?;
+                if stack.is_empty() && RESULTS.contains(&token.as_str()) {
+                    game.result = token;
+                    trees.push(tree.take().unwrap());
+                    tags.clear();
+                    at_start = true;
+                    continue;
+                }
+                if let Some(nag) = parse_nag(&token) {
+                    game.add_nag(NodeId(game.current), nag);
+                    continue;
+                }
+                let token: &str = strip_move_number(&token);
+                if token.is_empty() { continue; }
+                let san: &str = token.trim_end_matches(['!', '?']);
+                let m: Move = game.nodes[game.current].board.parse_san(san)
+                    .ok_or(PgnError::IllegalMove(game.ply(NodeId(game.current)), san.to_string()))?;
+                let child: usize = game.add_child(game.current, m);
+                game.current = child;
+                if let Some(nag) = parse_nag(&token[san.len()..]) {
+                    game.add_nag(NodeId(child), nag);
+                }
+                if !starting_comment.is_empty() {
+                    game.nodes[child].starting_comment = std::mem::take(&mut starting_comment);
+                }
+                at_start = false;
+            },
+        }
+    }
+    if !stack.is_empty() { return Err(PgnError::UnterminatedVariation); }
+    if let Some(done) = tree {
+        trees.push(done);
+    }
+    for game in trees.iter_mut() {
+        game.current = 0;
+    }
+    Ok(trees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_pgn;
+
+    const ANNOTATED: &str = r#"[Event "Paris"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+{The Opera Game} 1. e4 e5 2. Nf3 d6 3. d4 Bg4 $2 {This is a weak move already.} 4. dxe5 Bxf3 5. Qxf3 dxe5
+6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5 (9... Qb4 10. Qxb4 (10. a3? {[%eval -1.50] loses} Qxb3) 10... Na6)
+({Black can also try} 9... Na6) 10. Nxb5! cxb5 11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6
+15. Bxd7+ {[%clk 0:01:05.5] [%eval #2] [%csl Gd7]} Nxd7 16. Qb8+ !! Nxb8 17. Rd8# 1-0
+"#;
+
+    #[test]
+    fn parse_and_navigate() {
+        let trees = parse_pgn_tree(ANNOTATED).unwrap();
+        assert_eq!(trees.len(), 1);
+        let mut tree = trees[0].clone();
+        assert_eq!(tree.mainline().len(), 33);
+        assert_eq!(tree.result, "1-0");
+        assert_eq!(tree.comment(tree.root()), "The Opera Game");
+        assert!(tree.node_board(*tree.mainline().last().unwrap()).is_black_mated());
+
+        // 9... b5 has two variations
+        for _ in 0..18 { tree.go_next(); }
+        assert_eq!(tree.san(tree.current()), "b5");
+        assert!(tree.go_prev());
+        let moves: Vec<String> = tree.children(tree.current()).iter().map(|&node| tree.san(node).to_string()).collect();
+        assert_eq!(moves, ["b5", "Qb4", "Na6"]);
+        assert!(tree.enter_variation(1));
+        assert!(!tree.is_mainline(tree.current()));
+        assert!(tree.go_next());
+        assert_eq!(tree.san(tree.current()), "Qxb4");
+        let a3 = tree.children(tree.parent(tree.current()).unwrap())[1];
+        assert_eq!(tree.san(a3), "a3");
+        assert_eq!(tree.nags(a3), [2]);
+        assert_eq!(tree.eval(a3), Some(Score::Centipawns(-150)));
+        assert_eq!(tree.comment(a3), "loses");
+        assert_eq!(tree.children(tree.root()).len(), 1);
+
+        let mainline = tree.mainline();
+        assert_eq!(tree.starting_comment(tree.children(mainline[16])[2]), "Black can also try");
+        assert_eq!(tree.nags(mainline[5]), [2]);
+        assert_eq!(tree.nags(mainline[18]), [1]);
+        assert_eq!(tree.nags(mainline[30]), [3]);
+        assert_eq!(tree.clock(mainline[28]), Some(Duration::from_millis(65500)));
+        assert_eq!(tree.eval(mainline[28]), Some(Score::Mate(2)));
+        assert_eq!(tree.comment(mainline[28]), "[%csl Gd7]");
+
+        tree.go_to_start();
+        tree.go_to_end();
+        assert_eq!(tree.current(), *mainline.last().unwrap());
+        assert!(!tree.go_next());
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let tree = &parse_pgn_tree(ANNOTATED).unwrap()[0];
+        let text = tree.to_pgn();
+        assert!(text.starts_with("[Event \"Paris\"]\n"));
+        assert!(text.lines().all(|line| line.chars().count() <= 80));
+        // Lines are wrapped anywhere between tokens
+        let flat = text.replace('\n', " ");
+        assert!(flat.contains("{The Opera Game} 1. e4 e5"));
+        assert!(flat.contains("Bg4 $2 {This is a weak move already.} 4. dxe5"));
+        assert!(flat.contains("9. Bg5 b5 (9... Qb4 10. Qxb4 (10. a3 $2 {[%eval -1.50] loses} 10... Qxb3) 10... Na6) ({Black can also try} 9... Na6) 10. Nxb5 $1 cxb5"));
+        assert!(flat.contains("15. Bxd7+ {[%clk 0:01:05.5] [%eval #2] [%csl Gd7]} 15... Nxd7"));
+        assert!(flat.trim_end().ends_with("17. Rd8# 1-0"));
+        let again = &parse_pgn_tree(&text).unwrap()[0];
+        assert_eq!(again.to_pgn(), text);
+        assert_eq!(again.mainline_moves(), tree.mainline_moves());
+        assert_eq!(parse_pgn(&text).unwrap()[0].moves, tree.to_pgn_game().moves);
+
+        // From a position with black to move
+        let mut tree = GameTree::from_position(&ChessBoard::new_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40").unwrap());
+        tree.play_str("Kd7").unwrap();
+        tree.go_prev();
+        tree.play_str("Kf7").unwrap();
+        let text = tree.to_pgn();
+        assert!(text.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]"));
+        assert!(text.ends_with("\n40... Kd7 (40... Kf7) *\n"));
+        assert_eq!(parse_pgn_tree(&text).unwrap()[0].to_pgn(), text);
+
+        // Castling written with zeros, also inside a variation
+        let text = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 d6 (4...Nf6 5. d3 0-0) 5. d3 Bg4 6. Nc3 Qd7 7. Be3 0-0-0 *";
+        let tree = &parse_pgn_tree(text).unwrap()[0];
+        assert_eq!(tree.mainline_moves().len(), 14);
+        assert!(tree.to_pgn().replace('\n', " ").ends_with("4. O-O d6 (4... Nf6 5. d3 O-O) 5. d3 Bg4 6. Nc3 Qd7 7. Be3 O-O-O * "));
+    }
+
+    #[test]
+    fn edit_variations() {
+        let mut tree = GameTree::new();
+        let e4 = tree.play_str("e4").unwrap();
+        let e5 = tree.play_str("e5").unwrap();
+        tree.go_to(e4);
+        let c5 = tree.play_str("c5").unwrap();
+        let nf3 = tree.play_str("Nf3").unwrap();
+        tree.go_to(e4);
+        let e6 = tree.play_str("e6").unwrap();
+        // Playing a move that is there already goes to it
+        tree.go_to(e4);
+        assert_eq!(tree.play_str("c5"), Some(c5));
+        assert_eq!(tree.play_str("Ke3"), None);
+        assert_eq!(tree.children(e4), [e5, c5, e6]);
+
+        assert!(tree.promote_variation(e6));
+        assert_eq!(tree.children(e4), [e5, e6, c5]);
+        assert!(!tree.promote_variation(e5));
+        tree.promote_to_mainline(nf3);
+        assert_eq!(tree.children(e4), [c5, e5, e6]);
+        assert!(tree.is_mainline(nf3));
+        assert_eq!(tree.mainline(), [e4, c5, nf3]);
+
+        tree.go_to(nf3);
+        assert!(tree.delete_variation(c5));
+        assert_eq!(tree.current(), e4);
+        assert_eq!(tree.mainline(), [e4, e5]);
+        assert!(!tree.delete_variation(tree.root()));
+
+        // Deleted nodes keep their ids but are no longer part of the tree
+        assert!(!tree.contains(c5));
+        assert!(!tree.contains(nf3));
+        assert!(!tree.is_mainline(c5));
+        assert!(!tree.is_mainline(nf3));
+        assert!(!tree.promote_variation(c5));
+        tree.promote_to_mainline(nf3);
+        assert_eq!(tree.children(e4), [e5, e6]);
+        assert!(!tree.delete_variation(nf3));
+        // The only move after e5, so e5 has no moves left after deleting it
+        tree.go_to(e5);
+        let nc3 = tree.play_str("Nc3").unwrap();
+        assert!(tree.delete_variation(nc3));
+        assert!(!tree.is_mainline(nc3));
+        assert!(!tree.promote_variation(nc3));
+        tree.promote_to_mainline(nc3);
+        assert!(tree.children(e5).is_empty());
+
+        tree.set_comment(e5, "solid");
+        tree.add_nag(e5, 1);
+        tree.add_nag(e5, 1);
+        tree.add_nag(e5, 16);
+        tree.remove_nag(e5, 1);
+        tree.set_clock(e5, Some(Duration::from_secs(5400)));
+        tree.set_eval(e5, Some(Score::Centipawns(31)));
+        tree.set_starting_comment(e6, "or");
+        assert!(tree.to_pgn().ends_with("\n1. e4 e5 $16 {[%clk 1:30:00] [%eval 0.31] solid} ({or} 1... e6) *\n"));
+        assert_eq!(nag_symbol(16), Some("±"));
+        assert_eq!(parse_nag("±"), Some(16));
+        assert_eq!(parse_nag("$140"), Some(140));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(parse_pgn_tree("1. e4 (1. d4"), Err(PgnError::UnterminatedVariation)));
+        assert!(matches!(parse_pgn_tree("1. e4 e5)"), Err(PgnError::UnterminatedVariation)));
+        assert!(matches!(parse_pgn_tree("(1. d4) 1. e4"), Err(PgnError::UnterminatedVariation)));
+        assert!(matches!(parse_pgn_tree("1. e4 {open"), Err(PgnError::UnterminatedComment)));
+        assert!(matches!(parse_pgn_tree("1. e4 e5 (1... Ke3)"), Err(PgnError::IllegalMove(1, ref san)) if san == "Ke3"));
+        let trees = parse_pgn_tree("[White \"A\"]\n1. e4 *\n[White \"B\"]\n1. d4 1-0").unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[1].tag("White"), Some("B"));
+        assert_eq!(trees[1].result, "1-0");
+    }
+}
@@ -0,0 +1,247 @@
+use std::mem;
+
+use crate::{ChessBoard, Move};
+use crate::search::{MAX_PLY, TB_WIN_SCORE};
+
+// 12 * 64 piece keys, 64 castling rook keys, 8 en passant file keys and one for black to move
+const PIECE_KEYS: usize = 0;
+const CASTLING_KEYS: usize = 12 * 64;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 64;
+const BLACK_TO_MOVE_KEY: usize = EN_PASSANT_KEYS + 8;
+const KEY_COUNT: usize = BLACK_TO_MOVE_KEY + 1;
+
+// splitmix64, so the keys are the same on every run and every platform
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys: [u64; KEY_COUNT] = [0; KEY_COUNT];
+    let mut state: u64 = 0x9E3779B97F4A7C15;
+    let mut i: usize = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z: u64 = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+const KEYS: [u64; KEY_COUNT] = generate_keys();
+
+pub fn zobrist_key(board: &ChessBoard) -> u64 {
+    let pieces: [u64; 12] = [
+        board.white_king.value, board.white_queen.value, board.white_rook.value,
+        board.white_bishop.value, board.white_knight.value, board.white_pawn.value,
+        board.black_king.value, board.black_queen.value, board.black_rook.value,
+        board.black_bishop.value, board.black_knight.value, board.black_pawn.value];
+    let mut key: u64 = 0;
+
+    for (i, bb) in pieces.iter().enumerate() {
+        let mut bits: u64 = *bb;
+        while bits != 0 {
+            key ^= KEYS[PIECE_KEYS + i * 64 + bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+    }
+    let mut rights: u64 = board.castling_rights.value;
+    while rights != 0 {
+        key ^= KEYS[CASTLING_KEYS + rights.trailing_zeros() as usize];
+        rights &= rights - 1;
+    }
+    if let Some(square) = board.en_passant {
+        key ^= KEYS[EN_PASSANT_KEYS + square.column as usize];
+    }
+    if !board.white_turn {
+        key ^= KEYS[BLACK_TO_MOVE_KEY];
+    }
+    key
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    // The score is at least this high, the search failed high
+    Lower,
+    // The score is at most this high, no move raised alpha
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TtEntry {
+    pub key: u64,
+    pub depth: i32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    age: u8,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let mut tt: TranspositionTable = TranspositionTable { entries: Vec::new(), age: 0 };
+        tt.resize(megabytes);
+        tt
+    }
+
+    pub fn resize(&mut self, megabytes: usize) {
+        let count: usize = (megabytes * 1024 * 1024 / mem::size_of::<Option<TtEntry>>()).max(1);
+        self.entries = vec![None; count];
+        self.age = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.age = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Call before every new search, entries from older searches are the first to be replaced
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    // Mate and tablebase scores are stored relative to the node and converted back to the root on probing
+    pub fn probe(&self, key: u64, ply: i32) -> Option<TtEntry> {
+        match self.entries[self.index(key)] {
+            Some(entry) if entry.key == key => {
+                let mut entry: TtEntry = entry;
+                entry.score = score_from_tt(entry.score, ply);
+                Some(entry)
+            },
+            _ => None,
+        }
+    }
+
+    pub fn store(&mut self, key: u64, depth: i32, bound: Bound, score: i32, best_move: Option<Move>, ply: i32) {
+        let index: usize = self.index(key);
+        let mut best_move: Option<Move> = best_move;
+        if let Some(old) = self.entries[index] {
+            if old.key == key {
+                best_move = best_move.or(old.best_move);
+                if bound != Bound::Exact && old.age == self.age && old.depth > depth { return; }
+            } else if old.age == self.age && old.depth > depth {
+                return;
+            }
+        }
+        self.entries[index] = Some(TtEntry { key, depth, bound, score: score_to_tt(score, ply), best_move, age: self.age });
+    }
+
+    // Permill of the first thousand slots used by the current search, like UCI's hashfull
+    pub fn hashfull(&self) -> u32 {
+        let sample: usize = self.entries.len().min(1000);
+        let used: usize = self.entries[..sample].iter().filter(|entry| matches!(entry, Some(e) if e.age == self.age)).count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= TB_WIN_SCORE - MAX_PLY {
+        score + ply
+    } else if score <= -TB_WIN_SCORE + MAX_PLY {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= TB_WIN_SCORE - MAX_PLY {
+        score - ply
+    } else if score <= -TB_WIN_SCORE + MAX_PLY {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE_SCORE;
+    use crate::square_from_string;
+
+    #[test]
+    fn zobrist_transpositions() {
+        let mut a = ChessBoard::new();
+        let mut b = ChessBoard::new();
+        assert_eq!(zobrist_key(&a), ChessBoard::new().zobrist_key());
+
+        for (from, to) in [("G1", "F3"), ("G8", "F6"), ("B1", "C3")] {
+            a.make_move_string(from, to);
+        }
+        for (from, to) in [("B1", "C3"), ("G8", "F6"), ("G1", "F3")] {
+            b.make_move_string(from, to);
+        }
+        assert_eq!(a.zobrist_key(), b.zobrist_key());
+
+        // Same pieces, but the side to move, castling rights and en passant square all matter
+        let white = ChessBoard::new_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        for fen in [
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R b KQkq d6 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w Qkq d6 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq - 0 1"] {
+            assert_ne!(white.zobrist_key(), ChessBoard::new_fen(fen).unwrap().zobrist_key());
+        }
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        assert!(tt.len() > 1000);
+        assert_eq!(tt.hashfull(), 0);
+
+        let key: u64 = ChessBoard::new().zobrist_key();
+        let m = Move::new(square_from_string("E2".to_string()), square_from_string("E4".to_string()));
+        tt.store(key, 5, Bound::Exact, 30, Some(m), 0);
+        let entry = tt.probe(key, 0).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move), (5, Bound::Exact, 30, Some(m)));
+        assert_eq!(tt.probe(key ^ 1, 0), None);
+
+        // A shallower bound from the same search does not replace a deeper result
+        tt.store(key, 2, Bound::Lower, 90, None, 0);
+        assert_eq!(tt.probe(key, 0).unwrap().depth, 5);
+        // After a new search it does, keeping the best move around
+        tt.new_search();
+        tt.store(key, 2, Bound::Lower, 90, None, 0);
+        let entry = tt.probe(key, 0).unwrap();
+        assert_eq!((entry.depth, entry.best_move), (2, Some(m)));
+
+        for i in 0..tt.len() as u64 * 2 {
+            tt.store(i.wrapping_mul(0x9E3779B97F4A7C15), 1, Bound::Exact, 0, None, 0);
+        }
+        assert!(tt.hashfull() > 500);
+
+        tt.clear();
+        assert_eq!(tt.probe(key, 0), None);
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn mate_scores_are_ply_relative() {
+        let mut tt = TranspositionTable::new(1);
+        // Mate at ply 7 seen from a node at ply 3 is a mate in 4 plies from that node
+        tt.store(42, 4, Bound::Exact, MATE_SCORE - 7, None, 3);
+        assert_eq!(tt.probe(42, 3).unwrap().score, MATE_SCORE - 7);
+        assert_eq!(tt.probe(42, 1).unwrap().score, MATE_SCORE - 5);
+        tt.store(43, 4, Bound::Exact, -MATE_SCORE + 6, None, 2);
+        assert_eq!(tt.probe(43, 0).unwrap().score, -MATE_SCORE + 4);
+        tt.store(44, 4, Bound::Exact, TB_WIN_SCORE - 5, None, 2);
+        assert_eq!(tt.probe(44, 4).unwrap().score, TB_WIN_SCORE - 7);
+    }
+}
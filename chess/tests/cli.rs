@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run(input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chess-cli"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn move_formats() {
+    let output = run("play both\ne4\ne7e5\ng1-f3\nB8 C6\nbxc6\nfen\nquit\n");
+    assert!(output.contains("1. e4") && output.contains("1... e5") && output.contains("2. Nf3") && output.contains("2... Nc6"));
+    assert!(output.contains("unknown command or illegal move: bxc6"));
+    assert!(output.contains("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"));
+}
+
+#[test]
+fn board_and_moves() {
+    let output = run("moves\nflip\nunicode\n");
+    assert!(output.contains("Na3 Nc3 Nf3 Nh3 a3 a4 b3 b4"));
+    // White at the bottom first, then turned around, then with symbols
+    assert!(output.contains("1  R  N  B  Q  K  B  N  R\n   a  b  c  d  e  f  g  h"));
+    assert!(output.contains("8  r  n  b  k  q  b  n  r\n   h  g  f  e  d  c  b  a"));
+    assert!(output.contains("1  ♖  ♘  ♗  ♔  ♕  ♗  ♘  ♖"));
+}
+
+#[test]
+fn undo_and_results() {
+    let output = run("play both\nf3\ne5\ng4\nundo\nundo\nfen\ne5\ng4\nQh4#\npgn\n");
+    assert!(output.contains("rnbqkbnr/pppppppp/8/8/8/5P2/PPPPP1PP/RNBQKBNR b KQkq - 0 1"));
+    assert!(output.contains("0-1 {black mates}"));
+    // The last move and the checked king are marked
+    assert!(output.contains("4  .  .  .  .  .  .  P [q]\n"));
+    assert!(output.contains("1  R  N  B  Q (K) B  N  R\n"));
+    assert!(output.contains("1. f3 e5 2. g4 Qh4# 0-1"));
+
+    let output = run("play both\nfen 7k/8/6K1/8/8/8/8/5Q2 w - - 0 1\nQf8#\nundo\nQf7\n");
+    assert!(output.contains("1-0 {white mates}"));
+    assert!(output.contains("1/2-1/2 {stalemate}"));
+    let output = run("play both\nfen 8/8/8/8/8/4k3/3p4/2B1K3 w - - 0 1\nBxd2+\n");
+    assert!(output.contains("1/2-1/2 {insufficient material}"));
+}
+
+#[test]
+fn plays_the_engine() {
+    let output = run("level 2\nplay black\ne5\nundo\nfen\n");
+    assert!(output.contains("engine plays"));
+    // Undo takes back both the own move and the engine's reply
+    assert!(output.contains("/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1"));
+}
+
+#[test]
+fn save_and_load() {
+    let dir = std::env::temp_dir();
+    let pgn = dir.join(format!("chess-cli-{}.pgn", std::process::id()));
+    let fen = dir.join(format!("chess-cli-{}.fen", std::process::id()));
+    let output = run(&format!("play both\nd4\nd5\nsave {}\nsave {}\nnew\nload {}\nfen\nload {}\npgn\n",
+        pgn.display(), fen.display(), fen.display(), pgn.display()));
+    assert!(output.contains("rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq d6 0 2"));
+    assert!(output.contains("1. d4 d5 *"));
+    assert!(std::fs::read_to_string(&pgn).unwrap().contains("[Result \"*\"]"));
+    std::fs::remove_file(pgn).unwrap();
+    std::fs::remove_file(fen).unwrap();
+}
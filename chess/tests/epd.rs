@@ -0,0 +1,42 @@
+use std::process::Command;
+
+fn run(suite: &str, args: &[&str]) -> (String, i32) {
+    let path = std::env::temp_dir().join(format!("epd-runner-{}-{}.epd", std::process::id(), args.len()));
+    std::fs::write(&path, suite).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_epd-runner")).arg(&path).args(args).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    (String::from_utf8(output.stdout).unwrap(), output.status.code().unwrap())
+}
+
+#[test]
+fn runs_suite() {
+    let suite = "\
+# mate in one, a move to avoid and a wrong best move
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra8; id \"avoid\";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Kf1; id \"wrong\";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ke3; id \"illegal\";
+not an epd line
+";
+    let (output, code) = run(suite, &["--depth", "2"]);
+    assert_eq!(code, 0);
+    assert!(output.contains("mate: solved Ra8# (bm Ra8#) mate 1 depth 2"), "{}", output);
+    assert!(output.contains("avoid: failed Ra8# (am Ra8)"));
+    assert!(output.contains("wrong: failed Ra8# (bm Kf1)"));
+    assert!(output.contains("illegal: skipped, IllegalMove(\"bm\", \"Ke3\")"));
+    assert!(output.contains("line 6: skipped, InvalidFen"));
+    assert!(output.trim_end().lines().last().unwrap().starts_with("solved 1 of 3 (33.3%), failed 2, skipped 2, nodes "));
+
+    let (output, _) = run(suite, &["--depth", "2", "--quiet"]);
+    assert!(!output.contains("mate: solved"));
+}
+
+#[test]
+fn bad_arguments() {
+    let (_, code) = run("", &["--depth"]);
+    assert_eq!(code, 2);
+    let (_, code) = run("", &["--foo"]);
+    assert_eq!(code, 2);
+    let output = Command::new(env!("CARGO_BIN_EXE_epd-runner")).arg("does/not/exist.epd").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,103 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run(input: &str) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().lines().map(|line| line.to_string()).collect()
+}
+
+fn best_moves(output: &[String]) -> Vec<String> {
+    output.iter().filter(|line| line.starts_with("bestmove")).cloned().collect()
+}
+
+#[test]
+fn handshake() {
+    let output = run("uci\nisready\nquit\n");
+    assert!(output[0].starts_with("id name"));
+    assert!(output.iter().any(|line| line.starts_with("option name Hash type spin")));
+    assert_eq!(output[output.len() - 2], "uciok");
+    assert_eq!(output[output.len() - 1], "readyok");
+}
+
+#[test]
+fn finds_mate() {
+    let output = run("ucinewgame\nposition fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 3\n");
+    assert!(output.iter().any(|line| line.starts_with("info depth 1 score mate 1 nodes")));
+    assert_eq!(best_moves(&output), vec!["bestmove a1a8"]);
+}
+
+#[test]
+fn position_with_moves() {
+    // After 1.e4 f6 2.d4 g5 white mates with Qh5
+    let output = run("position startpos moves e2e4 f7f6 d2d4 g7g5\ngo depth 2\n");
+    assert_eq!(best_moves(&output), vec!["bestmove d1h5"]);
+
+    let output = run("position fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1h1 e8c8\nisready\n");
+    assert_eq!(output, vec!["readyok"]);
+    let output = run("position startpos moves e2e5\n");
+    assert_eq!(output, vec!["info string illegal move e2e5"]);
+}
+
+#[test]
+fn limits_and_stop() {
+    let output = run("position startpos\ngo nodes 2000\ngo movetime 100\ngo wtime 1000 btime 1000 winc 0 binc 0\ngo infinite\nstop\n");
+    assert_eq!(best_moves(&output).len(), 4);
+    // No bestmove for a search that is never stopped, until input ends
+    let output = run("position startpos\ngo\n");
+    assert_eq!(best_moves(&output).len(), 1);
+}
+
+#[test]
+fn ponder() {
+    let output = run("setoption name Ponder value true\nposition startpos moves e2e4\ngo ponder wtime 2000 btime 2000\nponderhit\n");
+    assert_eq!(best_moves(&output).len(), 1);
+}
+
+#[test]
+fn options() {
+    let output = run("setoption name Hash value 1\nsetoption name Clear Hash\nsetoption name UCI_Chess960 value true\nposition fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1h1\nsetoption name Foo value 1\n");
+    assert_eq!(output, vec!["info string unknown option Foo"]);
+}
+
+#[test]
+fn own_book() {
+    // One entry for the starting position: e2e4
+    let path = std::env::temp_dir().join(format!("uci-book-{}.bin", std::process::id()));
+    let mut entry: Vec<u8> = 0x463b96181691fc9cu64.to_be_bytes().to_vec();
+    entry.extend_from_slice(&(12u16 << 6 | 28).to_be_bytes());
+    entry.extend_from_slice(&[0, 1, 0, 0, 0, 0]);
+    std::fs::write(&path, entry).unwrap();
+
+    let output = run(&format!("setoption name Book File value {}\nsetoption name OwnBook value true\nposition startpos\ngo depth 3\nposition startpos moves e2e4\ngo depth 1\n", path.display()));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output[0], "info string book move");
+    assert_eq!(output[1], "bestmove e2e4");
+    // Out of book the engine searches
+    assert!(output[2].starts_with("info depth 1"));
+    assert_eq!(best_moves(&output).len(), 2);
+
+    let output = run("setoption name Book File value does/not/exist.bin\n");
+    assert!(output[0].starts_with("info string can not open book does/not/exist.bin"));
+}
+
+#[test]
+fn syzygy_path() {
+    let output = run("setoption name SyzygyPath value does/not/exist\n");
+    assert!(output[0].starts_with("info string can not open tablebases does/not/exist"));
+
+    // A directory without tables changes nothing
+    let path = std::env::temp_dir().join(format!("uci-syzygy-{}", std::process::id()));
+    std::fs::create_dir_all(&path).unwrap();
+    let output = run(&format!("setoption name SyzygyPath value {}\nposition fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n", path.display()));
+    std::fs::remove_dir(&path).unwrap();
+    assert_eq!(output[0], "info string found 0 tablebases up to 0 pieces");
+    assert!(output.iter().any(|line| line.contains(" tbhits 0 ")));
+    assert_eq!(best_moves(&output), vec!["bestmove a1a8"]);
+}